@@ -0,0 +1,88 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+use crate::color::RED;
+use crate::fov::Fov;
+use crate::game::{move_by, move_towards, mut_two, Game};
+use crate::object::{Object, PLAYER};
+
+/// 怪物的行为，每个回合由主循环驱动
+///
+/// `take_turn` 拿走当前的 AI 并返回下一回合使用的 AI，
+/// 这样可以在运行时替换成别的行为（例如混乱、逃跑），结束后再换回来。
+/// 实现时需要加上 `#[typetag::serde]`，存档才能保存怪物当前的 AI
+#[typetag::serde]
+pub trait Ai: Debug {
+    fn take_turn(
+        self: Box<Self>,
+        monster_id: usize,
+        fov: &dyn Fov,
+        game: &mut Game,
+        objects: &mut [Object],
+    ) -> Box<dyn Ai>;
+}
+
+/// 基础 AI：玩家在视野内时追击，相邻时攻击
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BasicAi;
+
+#[typetag::serde]
+impl Ai for BasicAi {
+    fn take_turn(
+        self: Box<Self>,
+        monster_id: usize,
+        fov: &dyn Fov,
+        game: &mut Game,
+        objects: &mut [Object],
+    ) -> Box<dyn Ai> {
+        // 怪物能看到玩家，玩家也就能看到怪物
+        let (monster_x, monster_y) = objects[monster_id].pos();
+        if fov.is_in_fov(monster_x, monster_y) {
+            if objects[monster_id].distance_to(&objects[PLAYER]) >= 2.0 {
+                // 向玩家移动
+                let (player_x, player_y) = objects[PLAYER].pos();
+                move_towards(monster_id, player_x, player_y, &game.map, objects);
+            } else if objects[PLAYER].fighter.is_some_and(|f| f.hp > 0) {
+                // 足够近，攻击（如果玩家还活着）
+                let (monster, player) = mut_two(monster_id, PLAYER, objects);
+                monster.attack(player, game);
+            }
+        }
+        self
+    }
+}
+
+/// 混乱 AI：随机移动若干回合，之后恢复原来的 AI
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfusedAi {
+    pub previous_ai: Box<dyn Ai>,
+    pub num_turns: i32,
+}
+
+#[typetag::serde]
+impl Ai for ConfusedAi {
+    fn take_turn(
+        mut self: Box<Self>,
+        monster_id: usize,
+        _fov: &dyn Fov,
+        game: &mut Game,
+        objects: &mut [Object],
+    ) -> Box<dyn Ai> {
+        if self.num_turns >= 0 {
+            // 仍然混乱，向随机方向移动
+            let dx = game.rng.gen_range(-1..=1);
+            let dy = game.rng.gen_range(-1..=1);
+            move_by(monster_id, dx, dy, &game.map, objects);
+            self.num_turns -= 1;
+            self
+        } else {
+            // 恢复原来的 AI
+            game.messages.add(
+                format!("The {} is no longer confused!", objects[monster_id].name),
+                RED,
+            );
+            self.previous_ai
+        }
+    }
+}
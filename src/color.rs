@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// RGB 颜色，与 tcod 的颜色一一对应，但不依赖 tcod
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+// 游戏逻辑用到的颜色，数值与 tcod::colors 中的同名常量相同
+pub const WHITE: Color = Color::new(255, 255, 255);
+pub const RED: Color = Color::new(255, 0, 0);
+pub const ORANGE: Color = Color::new(255, 127, 0);
+pub const VIOLET: Color = Color::new(127, 0, 255);
+pub const GREEN: Color = Color::new(0, 255, 0);
+pub const YELLOW: Color = Color::new(255, 255, 0);
+pub const LIGHT_YELLOW: Color = Color::new(255, 255, 63);
+pub const LIGHT_VIOLET: Color = Color::new(159, 63, 255);
+pub const LIGHT_GREEN: Color = Color::new(63, 255, 63);
+pub const LIGHT_CYAN: Color = Color::new(63, 255, 255);
+pub const DARK_RED: Color = Color::new(191, 0, 0);
+
+#[cfg(feature = "tcod")]
+impl From<Color> for tcod::colors::Color {
+    fn from(color: Color) -> Self {
+        tcod::colors::Color::new(color.r, color.g, color.b)
+    }
+}
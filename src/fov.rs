@@ -0,0 +1,13 @@
+/// 视野查询，游戏规则只需要知道某个位置当前是否可见
+///
+/// tcod 前端使用 `tcod::map::Map`，测试里可以用任何简单的实现代替
+pub trait Fov {
+    fn is_in_fov(&self, x: i32, y: i32) -> bool;
+}
+
+#[cfg(feature = "tcod")]
+impl Fov for tcod::map::Map {
+    fn is_in_fov(&self, x: i32, y: i32) -> bool {
+        tcod::map::Map::is_in_fov(self, x, y)
+    }
+}
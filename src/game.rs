@@ -0,0 +1,352 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp;
+
+use crate::color::{GREEN, RED, VIOLET, WHITE, YELLOW};
+use crate::fov::Fov;
+use crate::generation::make_map;
+use crate::map::{Door, Map, TileKind};
+use crate::messages::Messages;
+use crate::object::{DeathCallback, Fighter, Object, PLAYER};
+use crate::templates::Templates;
+
+/// 背包最多能装的物品数量，与菜单的 a-z 选项对应
+pub const MAX_INVENTORY: usize = 26;
+// 升级所需经验：LEVEL_UP_BASE + 当前等级 * LEVEL_UP_FACTOR
+const LEVEL_UP_BASE: i32 = 200;
+const LEVEL_UP_FACTOR: i32 = 150;
+// 升级时选择的属性加成
+pub const LEVEL_UP_HP: i32 = 20;
+pub const LEVEL_UP_POWER: i32 = 1;
+pub const LEVEL_UP_DEFENSE: i32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct Game {
+    pub map: Map,
+    /// 游戏过程中的随机数（例如混乱的怪物），地图由 `level_rng` 单独生成
+    pub rng: ChaCha8Rng,
+    pub seed: u64,
+    /// 当前所在的地牢层数，从 1 开始
+    pub dungeon_level: u32,
+    /// 显示在地图下方的消息日志
+    pub messages: Messages,
+    /// 玩家的背包
+    pub inventory: Vec<Object>,
+    /// 本回合改变了阻挡属性的瓦片（例如开关门），前端据此更新自己的视野地图后清空
+    #[serde(skip)]
+    pub changed_tiles: Vec<(i32, i32)>,
+}
+
+/// 玩家按键的结果，只有 `TookTurn` 会推进怪物和世界状态
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayerAction {
+    /// 行动花费的回合数，例如走进水里是 2
+    TookTurn(u32),
+    DidntTakeTurn,
+    Exit,
+}
+
+/// 升级时玩家可以选择提升的属性
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelUpChoice {
+    Hp,
+    Power,
+    Defense,
+}
+
+/// 用给定的种子创建一局新游戏，玩家位于 `objects[PLAYER]`
+pub fn new_game(seed: u64, templates: &Templates) -> (Game, Vec<Object>) {
+    let mut player = Object::new(0, 0, '@', "player", WHITE, true);
+    player.alive = true;
+    player.fighter = Some(Fighter {
+        base_max_hp: 30,
+        hp: 30,
+        base_defense: 2,
+        base_power: 5,
+        xp: 0,
+        on_death: DeathCallback::Player,
+    });
+    let mut objects: Vec<Object> = vec![player];
+    let mut game = Game {
+        map: vec![],
+        rng: ChaCha8Rng::seed_from_u64(seed),
+        seed,
+        dungeon_level: 1,
+        messages: Messages::new(),
+        inventory: vec![],
+        changed_tiles: vec![],
+    };
+    let mut rng = level_rng(seed, game.dungeon_level);
+    game.map = make_map(&mut objects, game.dungeon_level, templates, &mut rng);
+
+    // 欢迎消息
+    game.messages.add(
+        "Welcome stranger! Prepare to perish in the Tombs of the Ancient Kings.",
+        RED,
+    );
+
+    (game, objects)
+}
+
+/// 从 `level` 级升到下一级需要的经验值
+pub fn level_up_xp(level: i32) -> i32 {
+    LEVEL_UP_BASE + level * LEVEL_UP_FACTOR
+}
+
+/// 玩家经验足够时升一级并扣除对应的经验，返回是否升级
+///
+/// 升级后由调用者让玩家选择 `LevelUpChoice` 并调用 `level_up`；
+/// 经验足够升多级时需要重复调用
+pub fn check_level_up(game: &mut Game, objects: &mut [Object]) -> bool {
+    let player = &mut objects[PLAYER];
+    let level_up_xp = level_up_xp(player.level);
+    match player.fighter.as_mut() {
+        Some(fighter) if fighter.xp >= level_up_xp => {
+            fighter.xp -= level_up_xp;
+            player.level += 1;
+            game.messages.add(
+                format!(
+                    "Your battle skills grow stronger! You reached level {}!",
+                    player.level
+                ),
+                YELLOW,
+            );
+            true
+        }
+        _ => false,
+    }
+}
+
+/// 按玩家的选择提升属性
+pub fn level_up(choice: LevelUpChoice, objects: &mut [Object]) {
+    if let Some(fighter) = objects[PLAYER].fighter.as_mut() {
+        match choice {
+            LevelUpChoice::Hp => {
+                fighter.base_max_hp += LEVEL_UP_HP;
+                fighter.hp += LEVEL_UP_HP;
+            }
+            LevelUpChoice::Power => fighter.base_power += LEVEL_UP_POWER,
+            LevelUpChoice::Defense => fighter.base_defense += LEVEL_UP_DEFENSE,
+        }
+    }
+}
+
+/// 玩家是否站在楼梯上
+pub fn player_on_stairs(game: &Game, objects: &[Object]) -> bool {
+    let (x, y) = objects[PLAYER].pos();
+    game.map[x as usize][y as usize].kind == TileKind::Stairs
+}
+
+/// 进入下一层：重新生成地图，只保留玩家（仍然在 `PLAYER` 位置）
+///
+/// 调用者需要根据新地图重建 FOV
+pub fn next_level(game: &mut Game, objects: &mut Vec<Object>, templates: &Templates) {
+    game.messages.add(
+        "You take a moment to rest, and recover your strength.",
+        VIOLET,
+    );
+    let heal_hp = objects[PLAYER].max_hp(game) / 2;
+    objects[PLAYER].heal(heal_hp, game);
+
+    game.messages.add(
+        "After a rare moment of peace, you descend deeper into the heart of the dungeon...",
+        RED,
+    );
+    game.dungeon_level += 1;
+    game.changed_tiles.clear();
+    objects.truncate(PLAYER + 1);
+    let mut rng = level_rng(game.seed, game.dungeon_level);
+    game.map = make_map(objects, game.dungeon_level, templates, &mut rng);
+}
+
+/// 生成第 `level` 层地图的随机数，每层一个独立的流，
+/// 相同的种子总是生成相同的地牢，与游戏过程中用了多少随机数无关
+pub fn level_rng(seed: u64, level: u32) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(level as u64);
+    rng
+}
+
+/// 判断坐标是否被地图或阻挡的对象占据
+pub fn is_blocked(x: i32, y: i32, map: &Map, objects: &[Object]) -> bool {
+    if map[x as usize][y as usize].blocked() {
+        return true;
+    }
+    objects
+        .iter()
+        .any(|object| object.blocks && object.pos() == (x, y))
+}
+
+/// 移动给定的值
+pub fn move_by(id: usize, dx: i32, dy: i32, map: &Map, objects: &mut [Object]) {
+    let (x, y) = objects[id].pos();
+    if !is_blocked(x + dx, y + dy, map, objects) {
+        objects[id].set_pos(x + dx, y + dy);
+    }
+}
+
+/// 向目标位置移动一步
+pub fn move_towards(id: usize, target_x: i32, target_y: i32, map: &Map, objects: &mut [Object]) {
+    // 目标的向量和距离
+    let dx = target_x - objects[id].x;
+    let dy = target_y - objects[id].y;
+    let distance = ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
+
+    // 归一化为长度 1，再四舍五入为整数，保证移动限制在地图网格上
+    let dx = (dx as f32 / distance).round() as i32;
+    let dy = (dy as f32 / distance).round() as i32;
+    move_by(id, dx, dy, map, objects);
+}
+
+/// 所有带 AI 的怪物依次执行一个回合
+pub fn monsters_take_turn(fov: &dyn Fov, game: &mut Game, objects: &mut [Object]) {
+    for id in 0..objects.len() {
+        if objects[id].ai.is_some() {
+            ai_take_turn(id, fov, game, objects);
+        }
+    }
+}
+
+/// 玩家的行动花费了 `turns` 个回合，怪物同样行动这么多次，玩家死亡后停止
+pub fn monsters_take_turns(turns: u32, fov: &dyn Fov, game: &mut Game, objects: &mut [Object]) {
+    for _ in 0..turns {
+        monsters_take_turn(fov, game, objects);
+        if !objects[PLAYER].alive {
+            break;
+        }
+    }
+}
+
+/// 让怪物执行一个回合，并换上它返回的下一回合 AI
+fn ai_take_turn(monster_id: usize, fov: &dyn Fov, game: &mut Game, objects: &mut [Object]) {
+    if let Some(ai) = objects[monster_id].ai.take() {
+        let new_ai = ai.take_turn(monster_id, fov, game, objects);
+        // 回合内怪物可能已经死亡，死亡的怪物不再需要 AI
+        if objects[monster_id].alive {
+            objects[monster_id].ai = Some(new_ai);
+        }
+    }
+}
+
+/// 玩家移动，如果目标位置有可攻击的对象则攻击它
+///
+/// 返回花费的回合数：走进一格时是该地形的 `move_cost`，攻击、开门和撞墙都是 1
+pub fn player_move_or_attack(dx: i32, dy: i32, game: &mut Game, objects: &mut [Object]) -> u32 {
+    let x = objects[PLAYER].x + dx;
+    let y = objects[PLAYER].y + dy;
+
+    let target_id = objects
+        .iter()
+        .position(|object| object.fighter.is_some() && object.pos() == (x, y));
+
+    match target_id {
+        Some(target_id) => {
+            let (player, target) = mut_two(PLAYER, target_id, objects);
+            player.attack(target, game);
+            1
+        }
+        // 撞到关着的门时把它打开
+        None if game.map[x as usize][y as usize].door_state() == Some(Door::Closed) => {
+            set_door(x, y, Door::Open, game);
+            game.messages.add("You open the door.", WHITE);
+            1
+        }
+        None => {
+            move_by(PLAYER, dx, dy, &game.map, objects);
+            if objects[PLAYER].pos() == (x, y) {
+                game.map[x as usize][y as usize]
+                    .kind
+                    .info()
+                    .move_cost
+                    .unwrap_or(1)
+            } else {
+                1
+            }
+        }
+    }
+}
+
+/// 关上玩家身边一扇开着的门，门口有东西时不能关。返回是否关上了
+pub fn close_door(game: &mut Game, objects: &[Object]) -> bool {
+    let (player_x, player_y) = objects[PLAYER].pos();
+    let door = (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (player_x + dx, player_y + dy)))
+        .find(|&(x, y)| {
+            game.map[x as usize][y as usize].door_state() == Some(Door::Open)
+                && !objects.iter().any(|object| object.pos() == (x, y))
+        });
+    match door {
+        Some((x, y)) => {
+            set_door(x, y, Door::Closed, game);
+            game.messages.add("You close the door.", WHITE);
+            true
+        }
+        None => {
+            game.messages
+                .add("There is no open door you can close.", WHITE);
+            false
+        }
+    }
+}
+
+/// 改变门的状态，并记录到 `changed_tiles`
+fn set_door(x: i32, y: i32, door: Door, game: &mut Game) {
+    game.map[x as usize][y as usize].set_door(door);
+    game.changed_tiles.push((x, y));
+}
+
+/// 玩家脚下可以拾取的物品
+pub fn item_under_player(objects: &[Object]) -> Option<usize> {
+    objects
+        .iter()
+        .position(|object| object.pos() == objects[PLAYER].pos() && object.item.is_some())
+}
+
+/// 把物品放进背包并从地图上移除，背包满了则留在原地
+pub fn pick_item_up(object_id: usize, game: &mut Game, objects: &mut Vec<Object>) {
+    if game.inventory.len() >= MAX_INVENTORY {
+        game.messages.add(
+            format!(
+                "Your inventory is full, cannot pick up {}.",
+                objects[object_id].name
+            ),
+            RED,
+        );
+    } else {
+        let item = objects.swap_remove(object_id);
+        game.messages
+            .add(format!("You picked up a {}!", item.name), GREEN);
+        game.inventory.push(item);
+    }
+}
+
+/// 把背包中的物品放回玩家脚下
+pub fn drop_item(inventory_id: usize, game: &mut Game, objects: &mut Vec<Object>) {
+    let mut item = game.inventory.remove(inventory_id);
+    if item.equipment.is_some() {
+        item.dequip(&mut game.messages);
+        objects[PLAYER].clamp_hp(game);
+    }
+    let (x, y) = objects[PLAYER].pos();
+    item.set_pos(x, y);
+    game.messages
+        .add(format!("You dropped a {}.", item.name), YELLOW);
+    objects.push(item);
+}
+
+/// 同时可变借用切片中两个不同的元素
+pub(crate) fn mut_two<T>(
+    first_index: usize,
+    second_index: usize,
+    items: &mut [T],
+) -> (&mut T, &mut T) {
+    assert!(first_index != second_index);
+    let split_at_index = cmp::max(first_index, second_index);
+    let (first_slice, second_slice) = items.split_at_mut(split_at_index);
+    if first_index < second_index {
+        (&mut first_slice[first_index], &mut second_slice[0])
+    } else {
+        (&mut second_slice[0], &mut first_slice[second_index])
+    }
+}
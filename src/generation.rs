@@ -0,0 +1,312 @@
+use rand::{Rng, RngCore};
+use std::cmp;
+use std::collections::VecDeque;
+
+use crate::game::is_blocked;
+use crate::map::{Map, Rect, Tile, TileKind, MAP_HEIGHT, MAP_WIDTH};
+use crate::object::{Object, PLAYER};
+use crate::templates::Templates;
+
+mod bsp;
+mod caves;
+mod drunkard;
+mod rooms;
+mod validate;
+mod vaults;
+
+pub use bsp::Bsp;
+pub use caves::Caves;
+pub use drunkard::DrunkardsWalk;
+pub use rooms::RoomsAndCorridors;
+pub use validate::{validate_map, validate_rooms, LayoutError};
+
+// 生成的地图无法修复时重新生成，最多尝试这么多次
+const MAX_GENERATION_ATTEMPTS: i32 = 20;
+// 寻找空位的最多尝试次数，房间太挤时放弃放置
+const MAX_PLACEMENT_ATTEMPTS: i32 = 10;
+// 没有房间的地图按这个大小分块放置怪物和物品
+const AREA_WIDTH: i32 = 16;
+const AREA_HEIGHT: i32 = 15;
+
+/// 地图生成算法
+pub trait MapGenerator {
+    /// 生成一层的地形和放置对象需要的信息
+    fn generate(&self, rng: &mut dyn RngCore) -> Layout;
+}
+
+/// 地图生成器的结果
+pub struct Layout {
+    pub map: Map,
+    /// 玩家出现的位置
+    pub start: (i32, i32),
+    /// 下楼楼梯的位置
+    pub stairs: (i32, i32),
+    /// 放置怪物和物品的区域，每个区域调用一次 `place_objects`
+    ///
+    /// 房间生成器给出各个房间，没有房间的生成器给出包含地面的区域
+    pub rooms: Vec<Rect>,
+}
+
+/// 第 `level` 层使用的生成算法
+pub fn generator_for_level(level: u32) -> Box<dyn MapGenerator> {
+    // 第一层总是房间和走廊，之后轮流使用
+    match level % 4 {
+        1 => Box::new(RoomsAndCorridors),
+        2 => Box::new(Bsp),
+        3 => Box::new(Caves),
+        _ => Box::new(DrunkardsWalk),
+    }
+}
+
+/// 生成第 `level` 层的地图，怪物的数量和强度随层数增加
+pub fn make_map(
+    objects: &mut Vec<Object>,
+    level: u32,
+    templates: &Templates,
+    rng: &mut impl Rng,
+) -> Map {
+    let generator = generator_for_level(level);
+    make_map_with(generator.as_ref(), objects, level, templates, rng)
+}
+
+/// 用指定的生成算法生成第 `level` 层的地图，并放置玩家、怪物、物品和楼梯
+pub fn make_map_with(
+    generator: &dyn MapGenerator,
+    objects: &mut Vec<Object>,
+    level: u32,
+    templates: &Templates,
+    rng: &mut impl Rng,
+) -> Map {
+    let mut layout = generate_valid(generator, rng);
+
+    // 先放好玩家，怪物才不会生成在玩家身上
+    let (start_x, start_y) = layout.start;
+    objects[PLAYER].set_pos(start_x, start_y);
+
+    // 创建怪物和物品，预制房间自带怪物和物品
+    let avoid = [layout.start, layout.stairs];
+    for room in &layout.rooms {
+        let vault = vaults::try_place_vault(
+            *room,
+            &mut layout.map,
+            objects,
+            &avoid,
+            level,
+            templates,
+            rng,
+        );
+        if !vault {
+            place_objects(*room, &layout.map, objects, level, templates, rng);
+        }
+    }
+
+    // 放置下楼的楼梯
+    let (stairs_x, stairs_y) = layout.stairs;
+    layout.map[stairs_x as usize][stairs_y as usize] = Tile::new(TileKind::Stairs);
+
+    layout.map
+}
+
+/// 生成地图并修复，仍然不合格时重新生成
+///
+/// # Panics
+///
+/// 连续 `MAX_GENERATION_ATTEMPTS` 次都不合格时，说明生成算法本身有问题
+pub fn generate_valid(generator: &dyn MapGenerator, rng: &mut dyn RngCore) -> Layout {
+    let mut last_error = None;
+    for _ in 0..MAX_GENERATION_ATTEMPTS {
+        let mut layout = generator.generate(rng);
+        layout.repair();
+        match layout.validate() {
+            Ok(()) => return layout,
+            Err(err) => last_error = Some(err),
+        }
+    }
+    panic!(
+        "could not generate a valid map in {} attempts: {}",
+        MAX_GENERATION_ATTEMPTS,
+        last_error.expect("at least one attempt was made")
+    );
+}
+
+pub fn place_objects(
+    room: Rect,
+    map: &Map,
+    objects: &mut Vec<Object>,
+    level: u32,
+    templates: &Templates,
+    rng: &mut impl Rng,
+) {
+    // 越深的房间怪物越多
+    let max_monsters = templates.max_room_monsters(level);
+    let num_monsters = rng.gen_range(0..max_monsters + 1);
+    let monster_table = templates.monster_table(level);
+
+    for _ in 0..num_monsters {
+        // 找不到空位或者这一层没有可以出现的怪物时不放置
+        let (x, y) = match random_free_tile(room, map, objects, rng) {
+            Some(pos) => pos,
+            None => continue,
+        };
+        if let Some(monster) = monster_table.choose(rng) {
+            objects.push(monster.spawn(x, y, level));
+        }
+    }
+
+    let max_items = templates.max_room_items(level);
+    let num_items = rng.gen_range(0..max_items + 1);
+    let item_table = templates.item_table(level);
+
+    for _ in 0..num_items {
+        // 只放在没有被占据的位置
+        let (x, y) = match random_free_tile(room, map, objects, rng) {
+            Some(pos) => pos,
+            None => continue,
+        };
+        if let Some(item) = item_table.choose(rng) {
+            objects.push(item.spawn(x, y));
+        }
+    }
+}
+
+/// 在房间内部随机找一个没有被地图或阻挡对象占据的位置
+///
+/// 尝试 `MAX_PLACEMENT_ATTEMPTS` 次都失败时返回 `None`
+pub fn random_free_tile(
+    room: Rect,
+    map: &Map,
+    objects: &[Object],
+    rng: &mut impl Rng,
+) -> Option<(i32, i32)> {
+    (0..MAX_PLACEMENT_ATTEMPTS)
+        .map(|_| {
+            (
+                rng.gen_range(room.x1 + 1..room.x2),
+                rng.gen_range(room.y1 + 1..room.y2),
+            )
+        })
+        .find(|&(x, y)| !is_blocked(x, y, map, objects))
+}
+
+/// 全是墙的地图，生成器在上面挖出地面
+fn solid_map() -> Map {
+    vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize]
+}
+
+/// 把地图分成固定大小的块，含有地面的块作为放置怪物和物品的区域
+fn spawn_areas(map: &Map) -> Vec<Rect> {
+    let mut areas = vec![];
+    for x in (0..MAP_WIDTH).step_by(AREA_WIDTH as usize) {
+        for y in (0..MAP_HEIGHT).step_by(AREA_HEIGHT as usize) {
+            // 最后一列和一行的块截到地图边上
+            let w = AREA_WIDTH.min(MAP_WIDTH - 1 - x);
+            let h = AREA_HEIGHT.min(MAP_HEIGHT - 1 - y);
+            let area = Rect::new(x, y, w, h);
+            let has_floor = (area.x1 + 1..area.x2)
+                .any(|x| (area.y1 + 1..area.y2).any(|y| !map[x as usize][y as usize].blocked()));
+            if has_floor {
+                areas.push(area);
+            }
+        }
+    }
+    areas
+}
+
+/// 从 `start` 出发，经过四方向相邻的地面能走到的每个位置的步数，走不到的为 `None`
+///
+/// 门不论开关都算作可以通过
+pub fn flood_fill(map: &Map, start: (i32, i32)) -> Vec<Vec<Option<u32>>> {
+    let mut distances = vec![vec![None; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let (start_x, start_y) = start;
+    if !map[start_x as usize][start_y as usize].passable() {
+        return distances;
+    }
+    distances[start_x as usize][start_y as usize] = Some(0);
+    let mut queue = VecDeque::from([start]);
+    while let Some((x, y)) = queue.pop_front() {
+        let distance = distances[x as usize][y as usize].unwrap_or(0);
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= MAP_WIDTH || ny >= MAP_HEIGHT {
+                continue;
+            }
+            let (ux, uy) = (nx as usize, ny as usize);
+            if map[ux][uy].passable() && distances[ux][uy].is_none() {
+                distances[ux][uy] = Some(distance + 1);
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    distances
+}
+
+/// 在走廊穿过房间墙壁的地方放门
+///
+/// 只选择两侧是墙、前后分别通向房间和走廊的位置，不放在角上
+fn place_doors(rooms: &[Rect], map: &mut Map) {
+    let floor = |map: &Map, x: i32, y: i32| !map[x as usize][y as usize].blocked();
+    for room in rooms {
+        // 左右两面墙：上下是墙，左右是地面
+        for x in [room.x1, room.x2] {
+            for y in room.y1 + 1..room.y2 {
+                if x > 0
+                    && x < MAP_WIDTH - 1
+                    && floor(map, x, y)
+                    && !floor(map, x, y - 1)
+                    && !floor(map, x, y + 1)
+                    && floor(map, x - 1, y)
+                    && floor(map, x + 1, y)
+                {
+                    map[x as usize][y as usize] = Tile::door();
+                }
+            }
+        }
+        // 上下两面墙：左右是墙，上下是地面
+        for y in [room.y1, room.y2] {
+            for x in room.x1 + 1..room.x2 {
+                if y > 0
+                    && y < MAP_HEIGHT - 1
+                    && floor(map, x, y)
+                    && !floor(map, x - 1, y)
+                    && !floor(map, x + 1, y)
+                    && floor(map, x, y - 1)
+                    && floor(map, x, y + 1)
+                {
+                    map[x as usize][y as usize] = Tile::door();
+                }
+            }
+        }
+    }
+}
+
+/// 把一格挖成地面，最外一圈和地图外的位置保持不变
+fn carve(x: i32, y: i32, map: &mut Map) {
+    if x > 0 && y > 0 && x < MAP_WIDTH - 1 && y < MAP_HEIGHT - 1 {
+        map[x as usize][y as usize] = Tile::empty();
+    }
+}
+
+/// 将一个矩形放置在图上，并确保其地图快是空的
+pub fn create_room(room: Rect, map: &mut Map) {
+    for x in (room.x1 + 1)..room.x2 {
+        for y in (room.y1 + 1)..room.y2 {
+            carve(x, y, map);
+        }
+    }
+}
+
+// 创建水平隧道
+pub fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut Map) {
+    // `min()` 和 `max()` 用于 `x1 > x2` 的情况
+    // 确保..能有正确的值返回
+    for x in cmp::min(x1, x2)..(cmp::max(x1, x2) + 1) {
+        carve(x, y, map);
+    }
+}
+
+// 垂直隧道
+pub fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
+    for y in cmp::min(y1, y2)..(cmp::max(y1, y2) + 1) {
+        carve(x, y, map);
+    }
+}
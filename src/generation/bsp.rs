@@ -0,0 +1,118 @@
+//! 二叉空间分割：把地图递归切成小块，每块放一个房间，再连接兄弟块
+
+use rand::{Rng, RngCore};
+
+use super::{
+    create_h_tunnel, create_room, create_v_tunnel, place_doors, solid_map, Layout, MapGenerator,
+};
+use crate::map::{Map, Rect, MAP_HEIGHT, MAP_WIDTH};
+
+// 叶子的边长范围，小于两倍 MIN_LEAF_SIZE 的块不再切分
+const MIN_LEAF_SIZE: i32 = 12;
+const MAX_LEAF_SIZE: i32 = 24;
+// 房间的最小边长（包括墙）
+const ROOM_MIN_SIZE: i32 = 6;
+
+/// 切分地图直到每块足够小，每个叶子一个房间，兄弟子树之间用走廊连接，所有房间都相连
+pub struct Bsp;
+
+impl MapGenerator for Bsp {
+    fn generate(&self, rng: &mut dyn RngCore) -> Layout {
+        let mut map = solid_map();
+        let mut rooms = vec![];
+
+        // 右边和下边留出一格，房间的墙不会超出地图
+        let whole = Rect::new(0, 0, MAP_WIDTH - 1, MAP_HEIGHT - 1);
+        split(whole, rng, &mut map, &mut rooms);
+        place_doors(&rooms, &mut map);
+
+        // 玩家从第一个房间开始，楼梯在离它最远的房间
+        let start = rooms[0].center();
+        let distance = |room: &&Rect| {
+            let (x, y) = room.center();
+            (x - start.0).pow(2) + (y - start.1).pow(2)
+        };
+        let stairs = rooms
+            .iter()
+            .max_by_key(distance)
+            .expect("the map always has a room")
+            .center();
+
+        Layout {
+            map,
+            start,
+            stairs,
+            rooms,
+        }
+    }
+}
+
+/// 处理一个块：能切就切成两半并连接，否则放一个房间。返回这个子树中的一个房间
+fn split(leaf: Rect, rng: &mut dyn RngCore, map: &mut Map, rooms: &mut Vec<Rect>) -> Rect {
+    let w = leaf.x2 - leaf.x1;
+    let h = leaf.y2 - leaf.y1;
+    let can_split_x = w >= 2 * MIN_LEAF_SIZE;
+    let can_split_y = h >= 2 * MIN_LEAF_SIZE;
+    // 太大的块必须切，已经够小的块有一定机会保留
+    let small_enough = w <= MAX_LEAF_SIZE && h <= MAX_LEAF_SIZE;
+
+    if !(can_split_x || can_split_y) || (small_enough && rng.gen_bool(0.25)) {
+        let room = random_room(leaf, rng);
+        create_room(room, map);
+        rooms.push(room);
+        return room;
+    }
+
+    // 沿较长的一边切，形状接近时随机选择
+    let vertical = match (can_split_x, can_split_y) {
+        (true, false) => true,
+        (false, true) => false,
+        _ if w * 4 > h * 5 => true,
+        _ if h * 4 > w * 5 => false,
+        _ => rng.gen(),
+    };
+    let (first, second) = if vertical {
+        let at = rng.gen_range(MIN_LEAF_SIZE..=w - MIN_LEAF_SIZE);
+        (
+            Rect::new(leaf.x1, leaf.y1, at, h),
+            Rect::new(leaf.x1 + at, leaf.y1, w - at, h),
+        )
+    } else {
+        let at = rng.gen_range(MIN_LEAF_SIZE..=h - MIN_LEAF_SIZE);
+        (
+            Rect::new(leaf.x1, leaf.y1, w, at),
+            Rect::new(leaf.x1, leaf.y1 + at, w, h - at),
+        )
+    };
+
+    let first_room = split(first, rng, map, rooms);
+    let second_room = split(second, rng, map, rooms);
+
+    // 连接两个子树，各自的房间已经相连，所以整棵树都相连
+    let (x1, y1) = first_room.center();
+    let (x2, y2) = second_room.center();
+    if rng.gen() {
+        create_h_tunnel(x1, x2, y1, map);
+        create_v_tunnel(y1, y2, x2, map);
+    } else {
+        create_v_tunnel(y1, y2, x1, map);
+        create_h_tunnel(x1, x2, y2, map);
+    }
+
+    if rng.gen() {
+        first_room
+    } else {
+        second_room
+    }
+}
+
+/// 在叶子内随机大小和位置的房间
+fn random_room(leaf: Rect, rng: &mut dyn RngCore) -> Rect {
+    let leaf_w = leaf.x2 - leaf.x1;
+    let leaf_h = leaf.y2 - leaf.y1;
+    let w = rng.gen_range(ROOM_MIN_SIZE..=leaf_w);
+    let h = rng.gen_range(ROOM_MIN_SIZE..=leaf_h);
+    let x = leaf.x1 + rng.gen_range(0..=leaf_w - w);
+    let y = leaf.y1 + rng.gen_range(0..=leaf_h - h);
+    Rect::new(x, y, w, h)
+}
@@ -0,0 +1,171 @@
+//! 元胞自动机洞穴：随机填充墙壁，反复平滑成自然的洞穴形状
+
+use rand::{Rng, RngCore};
+
+use super::{flood_fill, solid_map, spawn_areas, Layout, MapGenerator};
+use crate::map::{Map, Tile, TileKind, MAP_HEIGHT, MAP_WIDTH};
+
+// 初始时地图内部是墙的比例
+const INITIAL_WALL_CHANCE: f64 = 0.45;
+// 平滑的次数
+const SMOOTHING_STEPS: i32 = 5;
+// 周围 8 格中至少这么多墙时变成墙，少于 WALL_DEATH_LIMIT 时变成地面
+const WALL_BIRTH_LIMIT: usize = 5;
+const WALL_DEATH_LIMIT: usize = 4;
+// 最大的连通洞穴至少占地图内部的这个比例，否则重新生成
+const MIN_CAVE_PERCENT: usize = 35;
+// 靠墙的地面变成碎石、其余地面长草的概率
+const RUBBLE_CHANCE: f64 = 0.2;
+const GRASS_CHANCE: f64 = 0.1;
+
+/// 元胞自动机生成的洞穴，只保留最大的连通区域，玩家和楼梯总在同一片洞穴里
+pub struct Caves;
+
+impl MapGenerator for Caves {
+    fn generate(&self, rng: &mut dyn RngCore) -> Layout {
+        let interior = ((MAP_WIDTH - 2) * (MAP_HEIGHT - 2)) as usize;
+        loop {
+            let mut map = random_fill(rng);
+            for _ in 0..SMOOTHING_STEPS {
+                map = smooth(&map);
+            }
+
+            // 找到最大的洞穴，其它不相连的小洞填成墙
+            let (start, size) = match largest_cave(&map) {
+                Some(cave) => cave,
+                None => continue,
+            };
+            if size * 100 < interior * MIN_CAVE_PERCENT {
+                continue;
+            }
+            let distances = flood_fill(&map, start);
+            for x in 0..MAP_WIDTH as usize {
+                for y in 0..MAP_HEIGHT as usize {
+                    if distances[x][y].is_none() {
+                        map[x][y] = Tile::wall();
+                    }
+                }
+            }
+
+            decorate(&mut map, rng);
+
+            // 楼梯放在走路最远的位置
+            let mut stairs = start;
+            let mut farthest = 0;
+            for x in 0..MAP_WIDTH {
+                for y in 0..MAP_HEIGHT {
+                    if let Some(distance) = distances[x as usize][y as usize] {
+                        if distance > farthest {
+                            farthest = distance;
+                            stairs = (x, y);
+                        }
+                    }
+                }
+            }
+
+            return Layout {
+                rooms: spawn_areas(&map),
+                map,
+                start,
+                stairs,
+            };
+        }
+    }
+}
+
+/// 随机把地图内部填成墙或地面，最外一圈始终是墙
+fn random_fill(rng: &mut dyn RngCore) -> Map {
+    let mut map = solid_map();
+    for x in 1..MAP_WIDTH - 1 {
+        for y in 1..MAP_HEIGHT - 1 {
+            if !rng.gen_bool(INITIAL_WALL_CHANCE) {
+                map[x as usize][y as usize] = Tile::empty();
+            }
+        }
+    }
+    map
+}
+
+/// 按周围墙的数量更新每一格，得到新的地图
+fn smooth(map: &Map) -> Map {
+    let mut next = solid_map();
+    for x in 1..MAP_WIDTH - 1 {
+        for y in 1..MAP_HEIGHT - 1 {
+            let walls = wall_neighbours(map, x, y);
+            let blocked = if walls >= WALL_BIRTH_LIMIT {
+                true
+            } else if walls < WALL_DEATH_LIMIT {
+                false
+            } else {
+                map[x as usize][y as usize].blocked()
+            };
+            if !blocked {
+                next[x as usize][y as usize] = Tile::empty();
+            }
+        }
+    }
+    next
+}
+
+/// 周围 8 格中墙的数量
+fn wall_neighbours(map: &Map, x: i32, y: i32) -> usize {
+    (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+        .filter(|&(dx, dy)| (dx, dy) != (0, 0))
+        .filter(|&(dx, dy)| map[(x + dx) as usize][(y + dy) as usize].blocked())
+        .count()
+}
+
+/// 给洞穴的地面加上碎石和草地，不改变哪些格子可以通过
+fn decorate(map: &mut Map, rng: &mut dyn RngCore) {
+    for x in 1..MAP_WIDTH - 1 {
+        for y in 1..MAP_HEIGHT - 1 {
+            if map[x as usize][y as usize].blocked() {
+                continue;
+            }
+            let (kind, chance) = if wall_neighbours(map, x, y) > 0 {
+                (TileKind::Rubble, RUBBLE_CHANCE)
+            } else {
+                (TileKind::Grass, GRASS_CHANCE)
+            };
+            if rng.gen_bool(chance) {
+                map[x as usize][y as usize] = Tile::new(kind);
+            }
+        }
+    }
+}
+
+/// 最大的连通洞穴中离地图中心最近的一格，以及洞穴的大小
+fn largest_cave(map: &Map) -> Option<((i32, i32), usize)> {
+    let mut seen = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let mut best: Option<((i32, i32), usize)> = None;
+    let center = (MAP_WIDTH / 2, MAP_HEIGHT / 2);
+    let to_center = |(x, y): (i32, i32)| (x - center.0).pow(2) + (y - center.1).pow(2);
+
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            if map[x as usize][y as usize].blocked() || seen[x as usize][y as usize] {
+                continue;
+            }
+            // 新的洞穴：标记所有相连的格子
+            let distances = flood_fill(map, (x, y));
+            let mut size = 0;
+            let mut closest = (x, y);
+            for cx in 0..MAP_WIDTH {
+                for cy in 0..MAP_HEIGHT {
+                    if distances[cx as usize][cy as usize].is_some() {
+                        seen[cx as usize][cy as usize] = true;
+                        size += 1;
+                        if to_center((cx, cy)) < to_center(closest) {
+                            closest = (cx, cy);
+                        }
+                    }
+                }
+            }
+            if best.is_none_or(|(_, best_size)| size > best_size) {
+                best = Some((closest, size));
+            }
+        }
+    }
+    best
+}
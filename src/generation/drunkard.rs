@@ -0,0 +1,60 @@
+//! 醉汉漫步：随机游走挖出一片弯曲相连的洞穴
+
+use rand::{Rng, RngCore};
+
+use super::{solid_map, spawn_areas, Layout, MapGenerator};
+use crate::map::{Tile, MAP_HEIGHT, MAP_WIDTH};
+
+// 挖到地图内部的这个比例为地面时停止
+const FLOOR_PERCENT: i32 = 40;
+// 每个醉汉最多走的步数
+const WALKER_STEPS: i32 = 400;
+
+/// 从地图中心开始随机游走，之后的醉汉都从已经挖开的地面出发，所以所有地面都相连
+pub struct DrunkardsWalk;
+
+impl MapGenerator for DrunkardsWalk {
+    fn generate(&self, rng: &mut dyn RngCore) -> Layout {
+        let mut map = solid_map();
+
+        // 最外一圈始终是墙
+        let target = (MAP_WIDTH - 2) * (MAP_HEIGHT - 2) * FLOOR_PERCENT / 100;
+        let start = (MAP_WIDTH / 2, MAP_HEIGHT / 2);
+        map[start.0 as usize][start.1 as usize] = Tile::empty();
+        let mut floor = vec![start];
+
+        while (floor.len() as i32) < target {
+            let (mut x, mut y) = floor[rng.gen_range(0..floor.len())];
+            for _ in 0..WALKER_STEPS {
+                let (dx, dy) = match rng.gen_range(0..4) {
+                    0 => (1, 0),
+                    1 => (-1, 0),
+                    2 => (0, 1),
+                    _ => (0, -1),
+                };
+                x = (x + dx).clamp(1, MAP_WIDTH - 2);
+                y = (y + dy).clamp(1, MAP_HEIGHT - 2);
+                let tile = &mut map[x as usize][y as usize];
+                if tile.blocked() {
+                    *tile = Tile::empty();
+                    floor.push((x, y));
+                }
+            }
+        }
+
+        // 楼梯放在离起点最远的地面上
+        let distance = |&(x, y): &(i32, i32)| (x - start.0).pow(2) + (y - start.1).pow(2);
+        let stairs = floor
+            .iter()
+            .copied()
+            .max_by_key(distance)
+            .expect("the start tile is always floor");
+
+        Layout {
+            rooms: spawn_areas(&map),
+            map,
+            start,
+            stairs,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! 随机房间和连接它们的 L 形走廊
+
+use rand::{Rng, RngCore};
+
+use super::{
+    create_h_tunnel, create_room, create_v_tunnel, place_doors, solid_map, Layout, MapGenerator,
+};
+use crate::map::{Rect, MAP_HEIGHT, MAP_WIDTH};
+
+const ROOM_MAX_SIZE: i32 = 10;
+const ROOM_MIN_SIZE: i32 = 6;
+const MAX_ROOMS: i32 = 30;
+
+/// 随机放置互不相交的房间，每个房间用走廊连到上一个房间
+pub struct RoomsAndCorridors;
+
+impl MapGenerator for RoomsAndCorridors {
+    fn generate(&self, rng: &mut dyn RngCore) -> Layout {
+        let mut map = solid_map();
+
+        let mut rooms: Vec<Rect> = vec![];
+
+        for _ in 0..MAX_ROOMS {
+            // 随机房间宽高
+            let w = rng.gen_range(ROOM_MIN_SIZE..ROOM_MAX_SIZE + 1);
+            let h = rng.gen_range(ROOM_MIN_SIZE..ROOM_MAX_SIZE + 1);
+            // 随机房间位置，保证在地图内
+            let x = rng.gen_range(0..MAP_WIDTH - w);
+            let y = rng.gen_range(0..MAP_HEIGHT - h);
+
+            let new_room = Rect::new(x, y, w, h);
+
+            // 判断所有已存在的房间是否和新创建的房间相交
+            let failed = rooms
+                .iter()
+                .any(|other_room| new_room.intersects_with(other_room));
+
+            if !failed {
+                // 有效房间，绘制在地图上
+                create_room(new_room, &mut map);
+
+                if let Some(prev_room) = rooms.last() {
+                    // 我们可以从一个水平隧道开始，到达与新房间相同的高度，然后与一个垂直隧道相连，或者我们可以做相反的事情:从一个垂直隧道开始，以一个水平隧道结束。
+                    let (new_x, new_y) = new_room.center();
+                    // 前一个房间的中心点
+                    let (prev_x, prev_y) = prev_room.center();
+
+                    // 随机 true 和 false 对应两种不同的通道方式
+                    if rng.gen() {
+                        create_h_tunnel(prev_x, new_x, prev_y, &mut map);
+                        create_v_tunnel(prev_y, new_y, new_x, &mut map);
+                    } else {
+                        create_v_tunnel(prev_y, new_y, prev_x, &mut map);
+                        create_h_tunnel(prev_x, new_x, new_y, &mut map);
+                    }
+                }
+
+                rooms.push(new_room);
+            }
+        }
+
+        place_doors(&rooms, &mut map);
+
+        // 玩家从第一个房间开始，楼梯在最后一个房间的中心
+        Layout {
+            map,
+            start: rooms[0].center(),
+            stairs: rooms[rooms.len() - 1].center(),
+            rooms,
+        }
+    }
+}
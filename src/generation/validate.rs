@@ -0,0 +1,162 @@
+//! 检查生成的地图：大小、边界、连通性和房间重叠
+
+use std::error::Error;
+use std::fmt;
+
+use super::{flood_fill, Layout};
+use crate::map::{Map, Rect, Tile, MAP_HEIGHT, MAP_WIDTH};
+
+/// 地图不满足的条件
+#[derive(Clone, Debug, PartialEq)]
+pub enum LayoutError {
+    /// 地图不是 `MAP_WIDTH` x `MAP_HEIGHT`
+    WrongSize,
+    /// 最外一圈有地面，走到那里会越界
+    OpenBorder { x: i32, y: i32 },
+    /// 玩家起点在地图外或者在墙里
+    BadStart { x: i32, y: i32 },
+    /// 楼梯在地图外、在墙里或者走不到
+    BadStairs { x: i32, y: i32 },
+    /// 这块地面从起点走不到
+    Unreachable { x: i32, y: i32 },
+    /// 房间超出地图
+    RoomOutOfBounds { index: usize },
+    /// 两个房间的内部重叠
+    RoomsOverlap { first: usize, second: usize },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::WrongSize => write!(f, "the map is not {}x{}", MAP_WIDTH, MAP_HEIGHT),
+            LayoutError::OpenBorder { x, y } => write!(f, "the border is open at ({}, {})", x, y),
+            LayoutError::BadStart { x, y } => {
+                write!(f, "the player start ({}, {}) is not walkable", x, y)
+            }
+            LayoutError::BadStairs { x, y } => {
+                write!(f, "the stairs at ({}, {}) cannot be reached", x, y)
+            }
+            LayoutError::Unreachable { x, y } => {
+                write!(
+                    f,
+                    "the floor at ({}, {}) is not connected to the start",
+                    x, y
+                )
+            }
+            LayoutError::RoomOutOfBounds { index } => {
+                write!(f, "room {} lies outside the map", index)
+            }
+            LayoutError::RoomsOverlap { first, second } => {
+                write!(f, "rooms {} and {} overlap", first, second)
+            }
+        }
+    }
+}
+
+impl Error for LayoutError {}
+
+impl Layout {
+    /// 检查整个地图，返回发现的第一个问题
+    pub fn validate(&self) -> Result<(), LayoutError> {
+        validate_map(&self.map, self.start, self.stairs)?;
+        validate_rooms(&self.rooms)
+    }
+
+    /// 修复可以修复的问题：封上边界，把走不到的地面填成墙。
+    /// 起点或楼梯有问题时无法修复，之后的 `validate` 会报告
+    pub fn repair(&mut self) {
+        if !size_ok(&self.map) {
+            return;
+        }
+        for x in 0..MAP_WIDTH {
+            for y in 0..MAP_HEIGHT {
+                if on_border(x, y) {
+                    self.map[x as usize][y as usize] = Tile::wall();
+                }
+            }
+        }
+        if !walkable(&self.map, self.start) {
+            return;
+        }
+        let distances = flood_fill(&self.map, self.start);
+        for (column, distances) in self.map.iter_mut().zip(distances) {
+            for (tile, distance) in column.iter_mut().zip(distances) {
+                if distance.is_none() {
+                    *tile = Tile::wall();
+                }
+            }
+        }
+    }
+}
+
+/// 检查地图大小、边界，以及所有地面和门（包括楼梯）都能从起点走到
+pub fn validate_map(map: &Map, start: (i32, i32), stairs: (i32, i32)) -> Result<(), LayoutError> {
+    if !size_ok(map) {
+        return Err(LayoutError::WrongSize);
+    }
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            if on_border(x, y) && map[x as usize][y as usize].passable() {
+                return Err(LayoutError::OpenBorder { x, y });
+            }
+        }
+    }
+    if !walkable(map, start) {
+        return Err(LayoutError::BadStart {
+            x: start.0,
+            y: start.1,
+        });
+    }
+
+    let distances = flood_fill(map, start);
+    let reached =
+        |(x, y): (i32, i32)| in_bounds(x, y) && distances[x as usize][y as usize].is_some();
+    if !reached(stairs) {
+        return Err(LayoutError::BadStairs {
+            x: stairs.0,
+            y: stairs.1,
+        });
+    }
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            if map[x as usize][y as usize].passable() && !reached((x, y)) {
+                return Err(LayoutError::Unreachable { x, y });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 房间在地图内，并且内部互不重叠（共用墙壁可以）
+pub fn validate_rooms(rooms: &[Rect]) -> Result<(), LayoutError> {
+    for (index, room) in rooms.iter().enumerate() {
+        if room.x1 < 0 || room.y1 < 0 || room.x2 >= MAP_WIDTH || room.y2 >= MAP_HEIGHT {
+            return Err(LayoutError::RoomOutOfBounds { index });
+        }
+    }
+    for (first, a) in rooms.iter().enumerate() {
+        for (second, b) in rooms.iter().enumerate().skip(first + 1) {
+            let overlap = a.x1 + 1 < b.x2 && b.x1 + 1 < a.x2 && a.y1 + 1 < b.y2 && b.y1 + 1 < a.y2;
+            if overlap {
+                return Err(LayoutError::RoomsOverlap { first, second });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn size_ok(map: &Map) -> bool {
+    map.len() == MAP_WIDTH as usize && map.iter().all(|column| column.len() == MAP_HEIGHT as usize)
+}
+
+fn in_bounds(x: i32, y: i32) -> bool {
+    x >= 0 && y >= 0 && x < MAP_WIDTH && y < MAP_HEIGHT
+}
+
+fn on_border(x: i32, y: i32) -> bool {
+    x == 0 || y == 0 || x == MAP_WIDTH - 1 || y == MAP_HEIGHT - 1
+}
+
+fn walkable(map: &Map, (x, y): (i32, i32)) -> bool {
+    in_bounds(x, y) && map[x as usize][y as usize].passable()
+}
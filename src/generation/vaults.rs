@@ -0,0 +1,100 @@
+//! 把预制房间盖到地图上
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::map::{Map, Rect, Tile};
+use crate::object::Object;
+use crate::templates::{
+    vault_terrain, Templates, VaultMarker, VaultTemplate, VAULT_ITEM, VAULT_MONSTER,
+};
+
+// 每个足够大的房间变成预制房间的概率
+const VAULT_CHANCE: f64 = 0.25;
+
+/// 有一定机会在房间里放一个预制房间，返回是否放置了
+///
+/// 只选择内部全是地面的房间，预制房间四周至少留一圈地面，原来的通道都不会被挡住。
+/// `avoid` 中的位置（玩家起点、楼梯）不会被覆盖
+pub fn try_place_vault(
+    room: Rect,
+    map: &mut Map,
+    objects: &mut Vec<Object>,
+    avoid: &[(i32, i32)],
+    level: u32,
+    templates: &Templates,
+    rng: &mut impl Rng,
+) -> bool {
+    if !rng.gen_bool(VAULT_CHANCE) || !is_open(room, map) {
+        return false;
+    }
+    // 房间内部去掉四周一圈后能放下的预制房间
+    let space_w = room.x2 - room.x1 - 3;
+    let space_h = room.y2 - room.y1 - 3;
+    let candidates: Vec<&VaultTemplate> = templates
+        .vaults
+        .iter()
+        .filter(|vault| vault.allowed_at(level))
+        .filter(|vault| vault.width() as i32 <= space_w && vault.height() as i32 <= space_h)
+        .collect();
+    let vault = match candidates.choose(rng) {
+        Some(vault) => *vault,
+        None => return false,
+    };
+
+    let (w, h) = (vault.width() as i32, vault.height() as i32);
+    let x = rng.gen_range(room.x1 + 2..=room.x2 - 1 - w);
+    let y = rng.gen_range(room.y1 + 2..=room.y2 - 1 - h);
+    let footprint = Rect::new(x, y, w, h);
+    let covers = |&(px, py): &(i32, i32)| {
+        px >= footprint.x1 && px < footprint.x2 && py >= footprint.y1 && py < footprint.y2
+    };
+    if avoid.iter().any(covers) {
+        return false;
+    }
+
+    stamp(vault, x, y, map, objects, level, templates, rng);
+    true
+}
+
+/// 房间内部是否全是地面
+fn is_open(room: Rect, map: &Map) -> bool {
+    (room.x1 + 1..room.x2)
+        .all(|x| (room.y1 + 1..room.y2).all(|y| !map[x as usize][y as usize].blocked()))
+}
+
+/// 以 `(left, top)` 为左上角画出预制房间，并在标记处放置怪物和物品
+#[allow(clippy::too_many_arguments)]
+fn stamp(
+    vault: &VaultTemplate,
+    left: i32,
+    top: i32,
+    map: &mut Map,
+    objects: &mut Vec<Object>,
+    level: u32,
+    templates: &Templates,
+    rng: &mut impl Rng,
+) {
+    let monster_table = templates.monster_table(level);
+    let item_table = templates.item_table(level);
+
+    for (dx, dy, glyph) in vault.cells() {
+        let (x, y) = (left + dx as i32, top + dy as i32);
+        map[x as usize][y as usize] = Tile::new(vault_terrain(glyph));
+
+        let object = match glyph {
+            VAULT_MONSTER => monster_table
+                .choose(rng)
+                .map(|monster| monster.spawn(x, y, level)),
+            VAULT_ITEM => item_table.choose(rng).map(|item| item.spawn(x, y)),
+            _ => match vault.legend.get(&glyph) {
+                Some(VaultMarker::Monster(name)) => templates
+                    .monster(name)
+                    .map(|monster| monster.spawn(x, y, level)),
+                Some(VaultMarker::Item(name)) => templates.item(name).map(|item| item.spawn(x, y)),
+                None => None,
+            },
+        };
+        objects.extend(object);
+    }
+}
@@ -0,0 +1,340 @@
+//! 物品的使用效果
+
+use crate::ai::ConfusedAi;
+use crate::color::{LIGHT_CYAN, LIGHT_GREEN, LIGHT_VIOLET, ORANGE, RED, WHITE};
+use crate::fov::Fov;
+use crate::game::Game;
+use crate::object::{Item, Object, Slot, PLAYER};
+
+// 治疗药水
+const HEAL_AMOUNT: i32 = 10;
+// 闪电卷轴
+const LIGHTNING_DAMAGE: i32 = 40;
+const LIGHTNING_RANGE: i32 = 5;
+// 混乱卷轴
+const CONFUSE_RANGE: i32 = 8;
+const CONFUSE_NUM_TURNS: i32 = 10;
+// 火球卷轴
+const FIREBALL_RANGE: i32 = 8;
+const FIREBALL_RADIUS: i32 = 3;
+const FIREBALL_DAMAGE: i32 = 12;
+
+/// 使用物品的结果
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UseResult {
+    /// 物品已经消耗，从背包移除
+    UsedUp,
+    /// 消耗了回合，但物品留在背包（例如装备）
+    UsedAndKept,
+    /// 没有产生效果，物品留在背包
+    Cancelled,
+}
+
+/// 物品需要玩家选择的目标种类
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TargetKind {
+    /// 地图上任意可见的位置
+    Tile,
+    /// 可见的怪物
+    Monster,
+}
+
+/// 物品向瞄准模式提出的要求
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TargetRequest {
+    pub kind: TargetKind,
+    /// 离玩家的最大距离，为空时不限制
+    pub max_range: Option<f32>,
+}
+
+/// 玩家在瞄准模式中选中的目标
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Tile(i32, i32),
+    /// `objects` 中的序号
+    Object(usize),
+}
+
+impl Target {
+    /// 目标所在的位置
+    pub fn pos(self, objects: &[Object]) -> (i32, i32) {
+        match self {
+            Target::Tile(x, y) => (x, y),
+            Target::Object(id) => objects[id].pos(),
+        }
+    }
+}
+
+impl Item {
+    /// 使用前需要玩家选择的目标，为空时直接使用
+    pub fn target_request(self) -> Option<TargetRequest> {
+        match self {
+            Item::Fireball => Some(TargetRequest {
+                kind: TargetKind::Tile,
+                max_range: Some(FIREBALL_RANGE as f32),
+            }),
+            Item::Confuse => Some(TargetRequest {
+                kind: TargetKind::Monster,
+                max_range: Some(CONFUSE_RANGE as f32),
+            }),
+            Item::Heal | Item::Lightning | Item::Equipment => None,
+        }
+    }
+}
+
+impl TargetRequest {
+    /// 检查 `(x, y)` 是否满足要求：在视野内、在范围内，需要怪物时那里要有怪物
+    pub fn select(&self, x: i32, y: i32, fov: &dyn Fov, objects: &[Object]) -> Option<Target> {
+        if !fov.is_in_fov(x, y) {
+            return None;
+        }
+        if let Some(max_range) = self.max_range {
+            if objects[PLAYER].distance(x, y) > max_range {
+                return None;
+            }
+        }
+        match self.kind {
+            TargetKind::Tile => Some(Target::Tile(x, y)),
+            TargetKind::Monster => objects
+                .iter()
+                .position(|obj| obj.pos() == (x, y) && obj.fighter.is_some() && obj.ai.is_some())
+                .map(Target::Object),
+        }
+    }
+}
+
+/// 使用背包中的物品
+///
+/// `target` 是玩家在瞄准模式中选择的目标，只有 `Item::target_request` 不为空的物品会用到。
+/// 所有效果只能作用于视野内的目标
+pub fn use_item(
+    inventory_id: usize,
+    target: Option<Target>,
+    fov: &dyn Fov,
+    game: &mut Game,
+    objects: &mut [Object],
+) -> UseResult {
+    use Item::*;
+
+    let item = match game.inventory[inventory_id].item {
+        Some(item) => item,
+        None => {
+            game.messages.add(
+                format!("The {} cannot be used.", game.inventory[inventory_id].name),
+                WHITE,
+            );
+            return UseResult::Cancelled;
+        }
+    };
+    let on_use = match item {
+        Heal => cast_heal,
+        Lightning => cast_lightning,
+        Confuse => cast_confuse,
+        Fireball => cast_fireball,
+        Equipment => toggle_equipment,
+    };
+    let result = on_use(inventory_id, target, fov, game, objects);
+    if result == UseResult::UsedUp {
+        game.inventory.remove(inventory_id);
+    }
+    result
+}
+
+fn cast_heal(
+    _inventory_id: usize,
+    _target: Option<Target>,
+    _fov: &dyn Fov,
+    game: &mut Game,
+    objects: &mut [Object],
+) -> UseResult {
+    // 治疗玩家
+    if let Some(fighter) = objects[PLAYER].fighter {
+        if fighter.hp == objects[PLAYER].max_hp(game) {
+            game.messages.add("You are already at full health.", RED);
+            return UseResult::Cancelled;
+        }
+        game.messages
+            .add("Your wounds start to feel better!", LIGHT_VIOLET);
+        objects[PLAYER].heal(HEAL_AMOUNT, game);
+        return UseResult::UsedUp;
+    }
+    UseResult::Cancelled
+}
+
+fn cast_lightning(
+    _inventory_id: usize,
+    _target: Option<Target>,
+    fov: &dyn Fov,
+    game: &mut Game,
+    objects: &mut [Object],
+) -> UseResult {
+    // 攻击最近的怪物
+    match closest_monster(LIGHTNING_RANGE, fov, objects) {
+        Some(monster_id) => {
+            game.messages.add(
+                format!(
+                    "A lightning bolt strikes the {} with a loud thunder! \
+                     The damage is {} hit points.",
+                    objects[monster_id].name, LIGHTNING_DAMAGE
+                ),
+                LIGHT_CYAN,
+            );
+            if let Some(xp) = objects[monster_id].take_damage(LIGHTNING_DAMAGE, game) {
+                gain_xp(xp, objects);
+            }
+            UseResult::UsedUp
+        }
+        None => {
+            game.messages
+                .add("No enemy is close enough to strike.", RED);
+            UseResult::Cancelled
+        }
+    }
+}
+
+fn cast_confuse(
+    _inventory_id: usize,
+    target: Option<Target>,
+    _fov: &dyn Fov,
+    game: &mut Game,
+    objects: &mut [Object],
+) -> UseResult {
+    // 混乱选中的怪物，暂时替换它的 AI
+    match target {
+        Some(Target::Object(monster_id)) if objects[monster_id].ai.is_some() => {
+            let previous_ai = objects[monster_id]
+                .ai
+                .take()
+                .expect("checked above that the monster has an AI");
+            objects[monster_id].ai = Some(Box::new(ConfusedAi {
+                previous_ai,
+                num_turns: CONFUSE_NUM_TURNS,
+            }));
+            game.messages.add(
+                format!(
+                    "The eyes of {} look vacant, as he starts to stumble around!",
+                    objects[monster_id].name
+                ),
+                LIGHT_GREEN,
+            );
+            UseResult::UsedUp
+        }
+        _ => {
+            game.messages.add("You must target a visible enemy.", RED);
+            UseResult::Cancelled
+        }
+    }
+}
+
+fn cast_fireball(
+    _inventory_id: usize,
+    target: Option<Target>,
+    fov: &dyn Fov,
+    game: &mut Game,
+    objects: &mut [Object],
+) -> UseResult {
+    // 只能向视野内的位置发射
+    let (x, y) = match target.map(|target| target.pos(objects)) {
+        Some((x, y)) if fov.is_in_fov(x, y) => (x, y),
+        _ => {
+            game.messages
+                .add("You can only target a visible tile.", RED);
+            return UseResult::Cancelled;
+        }
+    };
+    game.messages.add(
+        format!(
+            "The fireball explodes, burning everything within {} tiles!",
+            FIREBALL_RADIUS
+        ),
+        ORANGE,
+    );
+
+    // 爆炸范围内视野中的所有战斗对象都会受伤，包括玩家
+    let mut xp_to_gain = 0;
+    for (id, obj) in objects.iter_mut().enumerate() {
+        if obj.distance(x, y) <= FIREBALL_RADIUS as f32
+            && obj.fighter.is_some()
+            && fov.is_in_fov(obj.x, obj.y)
+        {
+            game.messages.add(
+                format!(
+                    "The {} gets burned for {} hit points.",
+                    obj.name, FIREBALL_DAMAGE
+                ),
+                ORANGE,
+            );
+            if let Some(xp) = obj.take_damage(FIREBALL_DAMAGE, game) {
+                // 烧死自己不会获得经验
+                if id != PLAYER {
+                    xp_to_gain += xp;
+                }
+            }
+        }
+    }
+    gain_xp(xp_to_gain, objects);
+
+    UseResult::UsedUp
+}
+
+/// 装备或卸下物品，同一栏位已有的装备会先被卸下
+fn toggle_equipment(
+    inventory_id: usize,
+    _target: Option<Target>,
+    _fov: &dyn Fov,
+    game: &mut Game,
+    objects: &mut [Object],
+) -> UseResult {
+    let equipment = match game.inventory[inventory_id].equipment {
+        Some(equipment) => equipment,
+        None => return UseResult::Cancelled,
+    };
+    if equipment.equipped {
+        game.inventory[inventory_id].dequip(&mut game.messages);
+    } else {
+        if let Some(current) = get_equipped_in_slot(equipment.slot, &game.inventory) {
+            game.inventory[current].dequip(&mut game.messages);
+        }
+        game.inventory[inventory_id].equip(&mut game.messages);
+    }
+
+    objects[PLAYER].clamp_hp(game);
+    UseResult::UsedAndKept
+}
+
+/// 背包中装备在某个栏位上的物品
+pub fn get_equipped_in_slot(slot: Slot, inventory: &[Object]) -> Option<usize> {
+    inventory.iter().position(|item| {
+        item.equipment
+            .is_some_and(|equipment| equipment.equipped && equipment.slot == slot)
+    })
+}
+
+/// 玩家用物品杀死怪物获得的经验值
+fn gain_xp(xp: i32, objects: &mut [Object]) {
+    if let Some(fighter) = objects[PLAYER].fighter.as_mut() {
+        fighter.xp += xp;
+    }
+}
+
+/// 在范围内、视野内，离玩家最近的怪物
+pub fn closest_monster(max_range: i32, fov: &dyn Fov, objects: &[Object]) -> Option<usize> {
+    let mut closest_enemy = None;
+    // 从比最大范围稍远的距离开始比较
+    let mut closest_dist = (max_range + 1) as f32;
+
+    for (id, object) in objects.iter().enumerate() {
+        if id != PLAYER
+            && object.fighter.is_some()
+            && object.ai.is_some()
+            && fov.is_in_fov(object.x, object.y)
+        {
+            let dist = objects[PLAYER].distance_to(object);
+            if dist < closest_dist {
+                closest_enemy = Some(id);
+                closest_dist = dist;
+            }
+        }
+    }
+    closest_enemy
+}
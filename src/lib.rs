@@ -0,0 +1,18 @@
+//! 游戏逻辑：地图、地牢生成、对象和回合规则
+//!
+//! 这里不依赖任何窗口，可以在没有显示器的机器上编译和测试，
+//! tcod 渲染前端在 `src/main.rs` 中。
+//! 关闭默认的 `tcod` feature 后，整个库不会链接 libtcod。
+
+pub mod ai;
+pub mod color;
+pub mod fov;
+pub mod game;
+pub mod generation;
+pub mod items;
+pub mod map;
+pub mod messages;
+pub mod object;
+pub mod save;
+pub mod spawn;
+pub mod templates;
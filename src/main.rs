@@ -1,272 +1,727 @@
-use rand::Rng;
-use std::cmp;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
 use tcod::map::{FovAlgorithm, Map as FovMap};
 
-use tcod::colors::{self, *};
+use tcod::colors::*;
 use tcod::console::*;
-use tcod::input::Key;
 use tcod::input::KeyCode::*;
+use tcod::input::{self, Event, Key, Mouse};
+
+use roguelike_rs::game::{
+    check_level_up, close_door, drop_item, item_under_player, level_up, level_up_xp,
+    monsters_take_turns, new_game, next_level, pick_item_up, player_move_or_attack,
+    player_on_stairs, Game, LevelUpChoice, PlayerAction, LEVEL_UP_DEFENSE, LEVEL_UP_HP,
+    LEVEL_UP_POWER,
+};
+use roguelike_rs::items::{use_item, UseResult};
+use roguelike_rs::map::{Map, MAP_HEIGHT, MAP_WIDTH};
+use roguelike_rs::messages::wrap;
+use roguelike_rs::object::{Object, PLAYER};
+use roguelike_rs::save::{load_game, save_game};
+use roguelike_rs::templates::Templates;
 
 // 窗口实际大小
 const SCREEN_WIDTH: i32 = 80;
 const SCREEN_HEIGHT: i32 = 50;
+// GUI 面板，位于地图下方
+const PANEL_HEIGHT: i32 = SCREEN_HEIGHT - MAP_HEIGHT;
+const PANEL_Y: i32 = SCREEN_HEIGHT - PANEL_HEIGHT;
+// 面板左侧的状态栏
+const BAR_WIDTH: i32 = 20;
+// 消息日志在状态栏右侧
+const MSG_X: i32 = BAR_WIDTH + 2;
+const MSG_WIDTH: i32 = SCREEN_WIDTH - BAR_WIDTH - 2;
+const MSG_HEIGHT: usize = PANEL_HEIGHT as usize;
 // 最大每秒20帧
 const LIMIT_FPS: i32 = 20;
-// 地图大小
-const MAP_WIDTH: i32 = 80;
-const MAP_HEIGHT: i32 = 45;
-// 地图颜色
-const COLOR_DARK_WALL: Color = Color { r: 0, g: 0, b: 100 };
-const COLOR_LIGHT_WALL: Color = Color {
-    r: 130,
-    g: 110,
-    b: 50,
-};
-const COLOR_DARK_GROUND: Color = Color {
-    r: 50,
-    g: 50,
-    b: 150,
-};
-const COLOR_LIGHT_GROUND: Color = Color {
-    r: 200,
-    g: 180,
-    b: 50,
-};
-// 地牢生成器
-const ROOM_MAX_SIZE: i32 = 10;
-const ROOM_MIN_SIZE: i32 = 6;
-const MAX_ROOMS: i32 = 30;
 // FOV
 const FOV_ALGO: FovAlgorithm = FovAlgorithm::Basic; // 默认FOV算法
 const FOV_LIGHT_WALLS: bool = true;
 const TORCH_RADIUS: i32 = 10;
-// 怪物数量
-const MAX_ROOM_MONSTERS: i32 = 3;
-// 玩家是第一位
-const PLAYER: usize = 0;
-
-type Map = Vec<Vec<Tile>>;
+// 存档文件，退出时自动保存
+const SAVE_FILE: &str = "savegame";
+// 怪物和物品模板
+const TEMPLATES_FILE: &str = "data/templates.json";
+// 菜单宽度
+const MAIN_MENU_WIDTH: i32 = 24;
+const INVENTORY_WIDTH: i32 = 50;
+const LEVEL_SCREEN_WIDTH: i32 = 40;
 
 // 与libtocd相关的值
 struct Tcod {
     root: Root,
     con: Offscreen,
+    panel: Offscreen,
     fov: FovMap,
+    mouse: Mouse,
 }
 
-struct Game {
-    map: Map,
+/// 界面所处的状态，主循环按状态分发输入和渲染
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GameState {
+    /// 主菜单，此时没有进行中的游戏
+    Menu,
+    /// 在地牢中行动
+    Playing,
+    /// 玩家已经死亡，只能查看地图或回到主菜单
+    Dead,
+    /// 打开背包选择一个物品
+    Inventory { mode: InventoryMode },
+    /// 在地图上移动光标选择一个位置
+    ///
+    /// `item` 是等待目标的背包物品序号，为空时只是查看光标下的对象
+    Targeting { x: i32, y: i32, item: Option<usize> },
+    /// 全屏查看消息记录，`scroll` 是从最新一行向上滚动的行数
+    MessageLog { scroll: usize },
+    /// 玩家升级，选择要提升的属性
+    LevelUp,
 }
 
-/// 这是一个通用对象的抽：玩家、怪物、物品、楼梯等
-/// 它始终由屏幕上的字符表示
-#[derive(Debug)]
-struct Object {
-    x: i32,
-    y: i32,
-    char: char,
-    color: Color,
-    name: String,
-    blocks: bool,
-    alive: bool,
+/// 打开背包的目的
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum InventoryMode {
+    /// 选择要使用的物品
+    Use,
+    /// 选择要丢下的物品
+    Drop,
 }
 
-/// 地图的瓦片和它的属性
-#[derive(Clone, Copy, Debug)]
-struct Tile {
-    /// 该块是否被阻挡无法移动到此处
-    blocked: bool,
-    /// 阻挡视线，目前定义：墙(true) false(地面)
-    block_sight: bool,
-    /// 战争迷雾
-    explored: bool,
+fn main() {
+    let templates = Templates::load(TEMPLATES_FILE).unwrap_or_else(|err| {
+        eprintln!("{}: {}", TEMPLATES_FILE, err);
+        std::process::exit(1);
+    });
+    // `--spawn-odds <层数>` 只打印该层的出现概率，不打开窗口
+    if let Some(level) = arg_from_args::<u32>("--spawn-odds", "a dungeon level") {
+        print!("{}", templates.describe_spawns(level));
+        return;
+    }
+
+    let root = Root::initializer()
+        .font("arial10x10.png", FontLayout::Tcod)
+        .font_type(FontType::Greyscale)
+        .size(SCREEN_WIDTH, SCREEN_HEIGHT)
+        .title("Rust/rouguelike")
+        .init();
+    // 离屏渲染
+    let mut tcod = Tcod {
+        root,
+        con: Offscreen::new(MAP_WIDTH, MAP_HEIGHT),
+        panel: Offscreen::new(SCREEN_WIDTH, PANEL_HEIGHT),
+        fov: FovMap::new(MAP_WIDTH, MAP_HEIGHT),
+        mouse: Default::default(),
+    };
+
+    tcod::system::set_fps(LIMIT_FPS);
+
+    let seed = arg_from_args("--seed", "an unsigned integer");
+    // 进行中的游戏，只有在主菜单时为空
+    let mut current: Option<(Game, Vec<Object>)> = None;
+    let mut state = GameState::Menu;
+
+    // 主循环
+    while !tcod.root.window_closed() {
+        state = match (state, current.as_mut()) {
+            (GameState::Menu, _) => match main_menu(&mut tcod, seed, &templates) {
+                Some((game, objects)) => {
+                    initialise_fov(&mut tcod, &game.map);
+                    recompute_fov(&mut tcod, &objects);
+                    current = Some((game, objects));
+                    GameState::Playing
+                }
+                None => break,
+            },
+            (state, Some((game, objects))) => {
+                // 清除离屏的上一次渲染
+                tcod.con.clear();
+                render_all(&mut tcod, game, objects);
+                let next = match state {
+                    GameState::Playing => play(&mut tcod, game, objects, &templates),
+                    GameState::Dead => dead(&mut tcod),
+                    GameState::Inventory { mode } => inventory(&mut tcod, game, objects, mode),
+                    GameState::Targeting { x, y, item } => {
+                        targeting(&mut tcod, game, objects, x, y, item)
+                    }
+                    GameState::MessageLog { scroll } => message_log(&mut tcod, game, scroll),
+                    GameState::LevelUp => level_up_menu(&mut tcod, game, objects),
+                    GameState::Menu => unreachable!(),
+                };
+                if next == GameState::Menu {
+                    // 回到主菜单前保存（或删除）存档
+                    save_on_exit(game, objects);
+                    current = None;
+                }
+                next
+            }
+            (state, None) => unreachable!("{:?} without a game in progress", state),
+        };
+    }
+
+    if let Some((game, objects)) = &current {
+        save_on_exit(game, objects);
+    }
 }
 
-/// 一个在地图上的矩形，用于表示房间
-#[derive(Clone, Copy, Debug)]
-struct Rect {
-    x1: i32,
-    y1: i32,
-    x2: i32,
-    y2: i32,
+/// 主菜单：新游戏、继续、退出。返回 `None` 表示退出
+fn main_menu(
+    tcod: &mut Tcod,
+    seed: Option<u64>,
+    templates: &Templates,
+) -> Option<(Game, Vec<Object>)> {
+    loop {
+        tcod.root.set_default_background(BLACK);
+        tcod.root.clear();
+        tcod.root.set_default_foreground(LIGHT_YELLOW);
+        tcod.root.print_ex(
+            SCREEN_WIDTH / 2,
+            SCREEN_HEIGHT / 2 - 6,
+            BackgroundFlag::None,
+            TextAlignment::Center,
+            "Rust/rouguelike",
+        );
+
+        let choices = &["New Game", "Continue", "Quit"];
+        let choice = menu("", choices, MAIN_MENU_WIDTH, &mut tcod.root);
+        if tcod.root.window_closed() {
+            return None;
+        }
+
+        match choice {
+            Some(0) => return Some(new_game(seed.unwrap_or_else(rand::random), templates)),
+            Some(1) => {
+                if !Path::new(SAVE_FILE).exists() {
+                    msgbox(
+                        "\nNo saved game to continue.\n",
+                        MAIN_MENU_WIDTH,
+                        &mut tcod.root,
+                    );
+                    continue;
+                }
+                match load_game(SAVE_FILE) {
+                    Ok(saved) => return Some(saved),
+                    Err(err) => {
+                        let text = format!("\nCould not continue: {}\n", err);
+                        msgbox(&text, INVENTORY_WIDTH, &mut tcod.root);
+                    }
+                }
+            }
+            Some(2) => return None,
+            _ => {}
+        }
+    }
 }
 
-impl Object {
-    /// 快捷方法创建一个对象
-    pub fn new(x: i32, y: i32, char: char, name: &str, color: Color, blocks: bool) -> Self {
-        Self {
-            x,
-            y,
-            char,
-            color,
-            name: name.into(),
-            blocks,
-            alive: false,
+/// 正常游戏状态：处理一个输入事件
+///
+/// 不阻塞等待按键，这样鼠标移动时面板上的名字也能及时更新
+fn play(
+    tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &mut Vec<Object>,
+    templates: &Templates,
+) -> GameState {
+    tcod.root.flush();
+    let key = match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
+        Some((_, Event::Key(key))) => key,
+        Some((_, Event::Mouse(mouse))) => {
+            tcod.mouse = mouse;
+            return GameState::Playing;
         }
+        None => return GameState::Playing,
+    };
+    // 每个字母键先来一个按键事件，再来一个 printable 相同的文本事件，只处理前者。
+    // '>' 只有文本事件，留给 `handle_keys`
+    if key.code == Text && key.text() != ">" {
+        return GameState::Playing;
     }
 
-    /// 移动给定的值
-    pub fn move_by(&mut self, dx: i32, dy: i32, game: &Game) {
-        if !game.map[(self.x + dx) as usize][(self.y + dy) as usize].blocked {
-            self.x += dx;
-            self.y += dy;
+    // 切换界面的按键，不消耗回合
+    match key {
+        Key { printable: 'i', .. } => {
+            return GameState::Inventory {
+                mode: InventoryMode::Use,
+            }
+        }
+        Key { printable: 'd', .. } => {
+            return GameState::Inventory {
+                mode: InventoryMode::Drop,
+            }
         }
+        Key { printable: 'm', .. } => return GameState::MessageLog { scroll: 0 },
+        Key { printable: 'l', .. } => {
+            let (x, y) = objects[PLAYER].pos();
+            return GameState::Targeting { x, y, item: None };
+        }
+        _ => {}
     }
 
-    /// 设置颜色然后再当前位置绘制对象的字符
-    // &mut dyn Console 这里的限定表示 con 只要实现 Console trait 即可。
-    // 这种限定方法叫 trait object
-    pub fn draw(&self, con: &mut dyn Console) {
-        con.set_default_foreground(self.color);
-        con.put_char(self.x, self.y, self.char, BackgroundFlag::None);
+    match handle_keys(key, tcod, game, objects, templates) {
+        PlayerAction::Exit => GameState::Menu,
+        PlayerAction::DidntTakeTurn => GameState::Playing,
+        PlayerAction::TookTurn(turns) => end_player_turn(tcod, game, objects, turns),
     }
+}
 
-    /// 获取对象位置
-    pub fn pos(&self) -> (i32, i32) {
-        (self.x, self.y)
+/// 玩家行动完成后：更新视野，怪物行动 `turns` 次，然后检查玩家是否死亡或升级
+fn end_player_turn(
+    tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &mut [Object],
+    turns: u32,
+) -> GameState {
+    update_fov_tiles(tcod, game);
+    recompute_fov(tcod, objects);
+    // 怪物回合，玩家走进水里或碎石上时怪物多走几步
+    monsters_take_turns(turns, &tcod.fov, game, objects);
+    if !objects[PLAYER].alive {
+        GameState::Dead
+    } else if check_level_up(game, objects) {
+        GameState::LevelUp
+    } else {
+        GameState::Playing
     }
+}
+
+/// 升级状态：必须选择一项属性，经验足够时连续升级
+fn level_up_menu(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> GameState {
+    let fighter = objects[PLAYER]
+        .fighter
+        .expect("the player always has a Fighter component");
+    let choices = &[
+        format!(
+            "Constitution (+{} HP, from {})",
+            LEVEL_UP_HP, fighter.base_max_hp
+        ),
+        format!(
+            "Strength (+{} attack, from {})",
+            LEVEL_UP_POWER, fighter.base_power
+        ),
+        format!(
+            "Agility (+{} defense, from {})",
+            LEVEL_UP_DEFENSE, fighter.base_defense
+        ),
+    ];
+    let choice = match menu(
+        "Level up! Choose a stat to raise:\n",
+        choices,
+        LEVEL_SCREEN_WIDTH,
+        &mut tcod.root,
+    ) {
+        Some(0) => LevelUpChoice::Hp,
+        Some(1) => LevelUpChoice::Power,
+        Some(2) => LevelUpChoice::Defense,
+        // 没有有效的选择时继续显示
+        _ => return GameState::LevelUp,
+    };
+    level_up(choice, objects);
 
-    /// 设置对象位置
-    pub fn set_pos(&mut self, x: i32, y: i32) {
-        self.x = x;
-        self.y = y;
+    if check_level_up(game, objects) {
+        GameState::LevelUp
+    } else {
+        GameState::Playing
     }
 }
 
-impl Tile {
-    pub fn empty() -> Self {
-        Self {
-            blocked: false,
-            block_sight: false,
-            explored: false,
-        }
+/// 死亡状态：地图保持显示，Esc 回到主菜单
+fn dead(tcod: &mut Tcod) -> GameState {
+    tcod.root.set_default_foreground(RED);
+    tcod.root.print_ex(
+        SCREEN_WIDTH / 2,
+        0,
+        BackgroundFlag::None,
+        TextAlignment::Center,
+        "You died! Press Escape to return to the main menu.",
+    );
+    tcod.root.flush();
+
+    match tcod.root.wait_for_keypress(true) {
+        Key { code: Escape, .. } => GameState::Menu,
+        _ => GameState::Dead,
     }
+}
 
-    pub fn wall() -> Self {
-        Self {
-            blocked: true,
-            block_sight: true,
-            explored: false,
+/// 背包状态：在地图上方显示背包，按选择的模式处理选中的物品
+fn inventory(
+    tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &mut Vec<Object>,
+    mode: InventoryMode,
+) -> GameState {
+    let header = match mode {
+        InventoryMode::Use => "Press the key next to an item to use it, or any other to cancel.\n",
+        InventoryMode::Drop => {
+            "Press the key next to an item to drop it, or any other to cancel.\n"
+        }
+    };
+    let choice = inventory_menu(&game.inventory, header, &mut tcod.root);
+
+    match (mode, choice) {
+        (InventoryMode::Use, Some(inventory_index)) => {
+            let needs_target = game.inventory[inventory_index]
+                .item
+                .is_some_and(|item| item.target_request().is_some());
+            if needs_target {
+                // 先选择目标，光标从玩家的位置开始
+                let (x, y) = objects[PLAYER].pos();
+                return GameState::Targeting {
+                    x,
+                    y,
+                    item: Some(inventory_index),
+                };
+            }
+            match use_item(inventory_index, None, &tcod.fov, game, objects) {
+                UseResult::UsedUp | UseResult::UsedAndKept => {
+                    end_player_turn(tcod, game, objects, 1)
+                }
+                UseResult::Cancelled => GameState::Playing,
+            }
+        }
+        (InventoryMode::Drop, Some(inventory_index)) => {
+            drop_item(inventory_index, game, objects);
+            end_player_turn(tcod, game, objects, 1)
         }
+        _ => GameState::Playing,
     }
 }
 
-impl Rect {
-    /// 创建一个矩形
-    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
-        Self {
-            x1: x,
-            y1: y,
-            x2: x + w,
-            y2: y + h,
-        }
+/// 以菜单显示背包中的物品，返回选中物品的序号
+fn inventory_menu(inventory: &[Object], header: &str, root: &mut Root) -> Option<usize> {
+    // 每个物品是一个选项
+    let options = if inventory.is_empty() {
+        vec!["Inventory is empty.".into()]
+    } else {
+        inventory
+            .iter()
+            .map(|item| match item.equipment {
+                // 显示装备着的栏位
+                Some(equipment) if equipment.equipped => {
+                    format!("{} (on {})", item.name, equipment.slot)
+                }
+                _ => item.name.clone(),
+            })
+            .collect()
+    };
+
+    let inventory_index = menu(header, &options, INVENTORY_WIDTH, root);
+
+    // 如果选中了一个物品则返回它
+    if inventory.is_empty() {
+        None
+    } else {
+        inventory_index
     }
+}
 
-    /// 获取中心点
-    pub fn center(&self) -> (i32, i32) {
-        let center_x = (self.x1 + self.x2) / 2;
-        let center_y = (self.y1 + self.y2) / 2;
-        (center_x, center_y)
+/// 瞄准状态：用鼠标或方向键移动光标，并显示光标下的对象
+///
+/// 有物品等待目标时，左键或 Enter 确认目标，右键或 Esc 取消；
+/// 目标必须满足物品的要求（视野内、范围内）。没有物品时只是查看
+fn targeting(
+    tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &mut [Object],
+    x: i32,
+    y: i32,
+    item: Option<usize>,
+) -> GameState {
+    let request = item
+        .and_then(|inventory_id| game.inventory[inventory_id].item)
+        .and_then(|item| item.target_request());
+
+    // 高亮光标所在的格子，颜色表示这里能否作为目标
+    let cursor_color = match request {
+        None => LIGHT_GREY,
+        Some(request) if request.select(x, y, &tcod.fov, objects).is_some() => LIGHT_GREEN,
+        Some(_) => LIGHT_RED,
+    };
+    tcod.root
+        .set_char_background(x, y, cursor_color, BackgroundFlag::Set);
+    let names = objects
+        .iter()
+        .filter(|obj| obj.pos() == (x, y) && tcod.fov.is_in_fov(obj.x, obj.y))
+        .map(|obj| obj.name.clone())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let header = match item {
+        Some(inventory_id) => format!(
+            "Target the {} (Enter to confirm, Escape to cancel): {}",
+            game.inventory[inventory_id].name, names
+        ),
+        None => names,
+    };
+    tcod.root.set_default_foreground(LIGHT_GREY);
+    tcod.root
+        .print_ex(1, 0, BackgroundFlag::None, TextAlignment::Left, header);
+    tcod.root.flush();
+
+    // 同时接受鼠标和键盘输入
+    let (dx, dy) = match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
+        Some((_, Event::Mouse(mouse))) => {
+            tcod.mouse = mouse;
+            if mouse.rbutton_pressed {
+                return GameState::Playing;
+            }
+            let (mouse_x, mouse_y) = (mouse.cx as i32, mouse.cy as i32);
+            // 鼠标在面板上时不移动光标
+            if mouse_x >= MAP_WIDTH || mouse_y >= MAP_HEIGHT {
+                return GameState::Targeting { x, y, item };
+            }
+            if mouse.lbutton_pressed {
+                return confirm_target(tcod, game, objects, mouse_x, mouse_y, item);
+            }
+            return GameState::Targeting {
+                x: mouse_x,
+                y: mouse_y,
+                item,
+            };
+        }
+        Some((_, Event::Key(key))) => match key {
+            Key { code: Up, .. } => (0, -1),
+            Key { code: Down, .. } => (0, 1),
+            Key { code: Left, .. } => (-1, 0),
+            Key { code: Right, .. } => (1, 0),
+            Key { code: Enter, .. } => return confirm_target(tcod, game, objects, x, y, item),
+            Key { code: Escape, .. } => return GameState::Playing,
+            _ => (0, 0),
+        },
+        None => (0, 0),
+    };
+    GameState::Targeting {
+        x: (x + dx).clamp(0, MAP_WIDTH - 1),
+        y: (y + dy).clamp(0, MAP_HEIGHT - 1),
+        item,
     }
+}
 
-    /// 如果一个图形与另一个图形相交返回 true
-    pub fn intersects_with(&self, other: &Rect) -> bool {
-        (self.x1 <= other.x2)
-            && (self.x2 >= other.x1)
-            && (self.y1 <= other.y2)
-            && (self.y2 >= other.y1)
+/// 确认光标位置为目标，把选中的位置或对象交给等待目标的物品
+fn confirm_target(
+    tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &mut [Object],
+    x: i32,
+    y: i32,
+    item: Option<usize>,
+) -> GameState {
+    let inventory_id = match item {
+        Some(inventory_id) => inventory_id,
+        // 只是查看，直接结束
+        None => return GameState::Playing,
+    };
+    let target = game.inventory[inventory_id]
+        .item
+        .and_then(|item| item.target_request())
+        .and_then(|request| request.select(x, y, &tcod.fov, objects));
+
+    match target {
+        Some(target) => match use_item(inventory_id, Some(target), &tcod.fov, game, objects) {
+            UseResult::UsedUp | UseResult::UsedAndKept => end_player_turn(tcod, game, objects, 1),
+            UseResult::Cancelled => GameState::Playing,
+        },
+        None => {
+            game.messages.add(
+                "That target is out of sight or out of range.",
+                roguelike_rs::color::RED,
+            );
+            GameState::Targeting { x, y, item }
+        }
     }
 }
 
-fn main() {
-    let root = Root::initializer()
-        .font("arial10x10.png", FontLayout::Tcod)
-        .font_type(FontType::Greyscale)
-        .size(SCREEN_WIDTH, SCREEN_HEIGHT)
-        .title("Rust/rouguelike")
-        .init();
-    // 离屏渲染
-    let mut tcod = Tcod {
-        root,
-        con: Offscreen::new(MAP_WIDTH, MAP_HEIGHT),
-        fov: FovMap::new(MAP_WIDTH, MAP_HEIGHT),
+/// 消息记录状态：全屏显示所有消息，方向键和翻页键滚动，Esc 关闭
+fn message_log(tcod: &mut Tcod, game: &Game, scroll: usize) -> GameState {
+    let lines = message_lines(game, SCREEN_WIDTH - 2);
+    // 第一行是标题，其余用来显示消息
+    let height = SCREEN_HEIGHT as usize - 2;
+    let max_scroll = lines.len().saturating_sub(height);
+    let scroll = scroll.min(max_scroll);
+
+    tcod.root.set_default_background(BLACK);
+    tcod.root.clear();
+    tcod.root.set_default_foreground(WHITE);
+    tcod.root.print_ex(
+        1,
+        0,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        "Message log (Up/Down, PageUp/PageDown to scroll, Escape to close)",
+    );
+    let end = lines.len() - scroll;
+    let start = end.saturating_sub(height);
+    for (y, (line, color)) in lines[start..end].iter().enumerate() {
+        tcod.root.set_default_foreground(*color);
+        tcod.root.print_ex(
+            1,
+            y as i32 + 2,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            line,
+        );
+    }
+    tcod.root.flush();
+
+    let scroll = match tcod.root.wait_for_keypress(true) {
+        Key { code: Up, .. } => scroll + 1,
+        Key { code: Down, .. } => scroll.saturating_sub(1),
+        Key { code: PageUp, .. } => scroll + height,
+        Key { code: PageDown, .. } => scroll.saturating_sub(height),
+        Key { code: Escape, .. } => return GameState::Playing,
+        _ => scroll,
     };
+    GameState::MessageLog {
+        scroll: scroll.min(max_scroll),
+    }
+}
 
-    tcod::system::set_fps(LIMIT_FPS);
+/// 所有消息按宽度换行后的每一行，从旧到新
+fn message_lines(game: &Game, width: i32) -> Vec<(String, Color)> {
+    game.messages
+        .iter()
+        .flat_map(|(message, color)| {
+            wrap(message, width as usize)
+                .into_iter()
+                .map(move |line| (line, Color::from(*color)))
+        })
+        .collect()
+}
 
-    let mut player = Object::new(0, 0, '@', "player", WHITE, true);
-    let mut objects: Vec<Object> = vec![player];
-    let mut game = Game {
-        map: make_map(&mut objects),
-    };
-    let mut previous_player_position = (-1, -1);
+/// 退出时保存游戏，玩家已经死亡则删除存档
+fn save_on_exit(game: &Game, objects: &[Object]) {
+    if objects[PLAYER].alive {
+        if let Err(err) = save_game(SAVE_FILE, game, objects) {
+            eprintln!("Failed to save the game: {}", err);
+        }
+    } else if let Err(err) = fs::remove_file(SAVE_FILE) {
+        if err.kind() != io::ErrorKind::NotFound {
+            eprintln!("Failed to remove the old save: {}", err);
+        }
+    }
+}
 
-    // FOV计算
+/// 按地图设置 FOV 的透明和可通行属性，新游戏和读档后都需要调用
+fn initialise_fov(tcod: &mut Tcod, map: &Map) {
     for y in 0..MAP_HEIGHT {
         for x in 0..MAP_WIDTH {
             tcod.fov.set(
                 x,
                 y,
-                !game.map[x as usize][y as usize].block_sight,
-                !game.map[x as usize][y as usize].blocked,
+                !map[x as usize][y as usize].block_sight(),
+                !map[x as usize][y as usize].blocked(),
             )
         }
     }
+}
 
-    // 主循环
-    while !tcod.root.window_closed() {
-        // 清除离屏的上一次渲染
-        tcod.con.clear();
-        let fov_recompute = previous_player_position != (objects[PLAYER].pos());
-        render_all(&mut tcod, &mut game, &objects, fov_recompute);
-        tcod.root.flush();
-
-        let player = &mut objects[PLAYER];
-        previous_player_position = (player.x, player.y);
-        let exit = handle_keys(&mut tcod, &game, player);
-
-        if exit {
-            break;
-        }
+/// 把本回合改变的瓦片同步到 FOV 地图，不需要重建整个地图
+fn update_fov_tiles(tcod: &mut Tcod, game: &mut Game) {
+    for (x, y) in game.changed_tiles.drain(..) {
+        let tile = &game.map[x as usize][y as usize];
+        tcod.fov.set(x, y, !tile.block_sight(), !tile.blocked());
     }
 }
 
-fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_recompute: bool) {
-    if fov_recompute {
-        let player = &objects[PLAYER];
-        tcod.fov
-            .compute_fov(player.x, player.y, TORCH_RADIUS, FOV_LIGHT_WALLS, FOV_ALGO);
+/// 以玩家为中心重新计算视野
+fn recompute_fov(tcod: &mut Tcod, objects: &[Object]) {
+    let player = &objects[PLAYER];
+    tcod.fov
+        .compute_fov(player.x, player.y, TORCH_RADIUS, FOV_LIGHT_WALLS, FOV_ALGO);
+}
+
+/// 显示一个带字母选项的菜单，返回选中的序号
+fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32, root: &mut Root) -> Option<usize> {
+    assert!(
+        options.len() <= 26,
+        "Cannot have a menu with more than 26 options."
+    );
+
+    // 计算标题自动换行后的高度，以及每个选项一行
+    let header_height = if header.is_empty() {
+        0
+    } else {
+        root.get_height_rect(0, 0, width, SCREEN_HEIGHT, header)
+    };
+    let height = options.len() as i32 + header_height;
+
+    // 在离屏窗口中绘制菜单
+    let mut window = Offscreen::new(width, height);
+    window.set_default_foreground(WHITE);
+    window.print_rect_ex(
+        0,
+        0,
+        width,
+        height,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        header,
+    );
+    for (index, option_text) in options.iter().enumerate() {
+        let menu_letter = (b'a' + index as u8) as char;
+        let text = format!("({}) {}", menu_letter, option_text.as_ref());
+        window.print_ex(
+            0,
+            header_height + index as i32,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            text,
+        );
+    }
+
+    // 居中拷贝到屏幕上，背景半透明
+    let x = SCREEN_WIDTH / 2 - width / 2;
+    let y = SCREEN_HEIGHT / 2 - height / 2;
+    blit(&window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
+    root.flush();
+
+    // 等待按键，把字母转换成序号
+    let key = root.wait_for_keypress(true);
+    if key.printable.is_ascii_lowercase() {
+        let index = key.printable as usize - 'a' as usize;
+        if index < options.len() {
+            return Some(index);
+        }
     }
+    None
+}
+
+/// 显示一段文字，任意键关闭
+fn msgbox(text: &str, width: i32, root: &mut Root) {
+    let options: &[&str] = &[];
+    menu(text, options, width, root);
+}
+
+fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object]) {
     // 遍历所有瓦片并设置他们的背景颜色
     for y in 0..MAP_HEIGHT {
         for x in 0..MAP_WIDTH {
             let visible = tcod.fov.is_in_fov(x, y);
-            let wall = game.map[x as usize][y as usize].block_sight;
-
-            let color = match (visible, wall) {
-                // 视野之外
-                (false, true) => COLOR_DARK_WALL,
-                (false, false) => COLOR_DARK_GROUND,
-                // 视野之内
-                (true, true) => COLOR_LIGHT_WALL,
-                (true, false) => COLOR_LIGHT_GROUND,
-            };
-            let explored = &mut game.map[x as usize][y as usize].explored;
+            let tile = &mut game.map[x as usize][y as usize];
             if visible {
-                *explored = true;
+                tile.explored = true;
             }
-            if *explored {
-                tcod.con
-                    .set_char_background(x, y, color, BackgroundFlag::Set)
+            if tile.explored {
+                // 字符和颜色都由地形决定，视野之外用暗色
+                let info = tile.kind.info();
+                let (fg, bg) = if visible {
+                    (info.light_fg, info.light_bg)
+                } else {
+                    (info.dark_fg, info.dark_bg)
+                };
+                tcod.con.put_char_ex(x, y, info.glyph, fg.into(), bg.into());
             }
         }
     }
 
-    for object in objects {
-        object.draw(&mut tcod.con);
+    // 先绘制不阻挡的对象（尸体），保证活着的对象画在上面
+    let mut to_draw: Vec<_> = objects.iter().collect();
+    to_draw.sort_by_key(|o| o.blocks);
+    for object in &to_draw {
+        draw_object(object, &mut tcod.con);
     }
 
     // 拷贝后台渲染到前台
+    tcod.root.set_default_background(BLACK);
+    tcod.root.clear();
     blit(
         &tcod.con,
         (0, 0),
@@ -276,121 +731,211 @@ fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_recomput
         1.0,
         1.0,
     );
-}
 
-fn handle_keys(tcod: &mut Tcod, game: &Game, player: &mut Object) -> bool {
-    let key = tcod.root.wait_for_keypress(true);
-    match key {
-        Key { code: Up, .. } => player.move_by(0, -1, game),
-        Key { code: Down, .. } => player.move_by(0, 1, game),
-        Key { code: Left, .. } => player.move_by(-1, 0, game),
-        Key { code: Right, .. } => player.move_by(1, 0, game),
-        Key {
-            code: Enter,
-            alt: true,
-            ..
-        } => {
-            let fullscreen = tcod.root.is_fullscreen();
-            tcod.root.set_fullscreen(!fullscreen);
-        }
-        Key { code: Escape, .. } => return true,
-        _ => {}
-    }
+    // 准备 GUI 面板
+    tcod.panel.set_default_background(BLACK);
+    tcod.panel.clear();
+
+    // 状态栏：鼠标下的对象名字、HP 条、层数、经验和种子
+    tcod.panel.set_default_foreground(LIGHT_GREY);
+    tcod.panel.print_ex(
+        1,
+        0,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        get_names_under_mouse(tcod.mouse, objects, &tcod.fov),
+    );
 
-    false
-}
+    let player = objects[PLAYER]
+        .fighter
+        .expect("the player always has a Fighter component");
+    render_bar(
+        &mut tcod.panel,
+        1,
+        1,
+        BAR_WIDTH,
+        "HP",
+        player.hp,
+        objects[PLAYER].max_hp(game),
+        LIGHT_RED,
+        DARKER_RED,
+    );
 
-fn make_map(objects: &mut Vec<Object>) -> Map {
-    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    tcod.panel.set_default_foreground(WHITE);
+    tcod.panel.print_ex(
+        1,
+        2,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!("Dungeon level: {}", game.dungeon_level),
+    );
+    tcod.panel.print_ex(
+        1,
+        3,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!(
+            "Lv {} XP: {}/{}",
+            objects[PLAYER].level,
+            player.xp,
+            level_up_xp(objects[PLAYER].level)
+        ),
+    );
+    tcod.panel.set_default_foreground(DARK_GREY);
+    tcod.panel.print_ex(
+        1,
+        4,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!("Seed: {}", game.seed),
+    );
 
-    let mut rooms = vec![];
+    // 显示最新的几行消息
+    let lines = message_lines(game, MSG_WIDTH);
+    let first = lines.len().saturating_sub(MSG_HEIGHT);
+    for (y, (line, color)) in lines[first..].iter().enumerate() {
+        tcod.panel.set_default_foreground(*color);
+        tcod.panel.print_ex(
+            MSG_X,
+            y as i32,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            line,
+        );
+    }
+
+    // 拷贝面板到地图下方
+    blit(
+        &tcod.panel,
+        (0, 0),
+        (SCREEN_WIDTH, PANEL_HEIGHT),
+        &mut tcod.root,
+        (0, PANEL_Y),
+        1.0,
+        1.0,
+    );
+}
 
-    for _ in 0..MAX_ROOMS {
-        // 随机房间宽高
-        let w = rand::thread_rng().gen_range(ROOM_MIN_SIZE..ROOM_MAX_SIZE + 1);
-        let h = rand::thread_rng().gen_range(ROOM_MIN_SIZE..ROOM_MAX_SIZE + 1);
-        // 随机房间位置，保证在地图内
-        let x = rand::thread_rng().gen_range(0..MAP_WIDTH - w);
-        let y = rand::thread_rng().gen_range(0..MAP_HEIGHT - h);
+/// 绘制一个状态条（HP、经验等），先画背景再画当前值
+#[allow(clippy::too_many_arguments)]
+fn render_bar(
+    panel: &mut Offscreen,
+    x: i32,
+    y: i32,
+    total_width: i32,
+    name: &str,
+    value: i32,
+    maximum: i32,
+    bar_color: Color,
+    back_color: Color,
+) {
+    let bar_width = (value as f32 / maximum as f32 * total_width as f32) as i32;
+
+    panel.set_default_background(back_color);
+    panel.rect(x, y, total_width, 1, false, BackgroundFlag::Screen);
+
+    panel.set_default_background(bar_color);
+    if bar_width > 0 {
+        panel.rect(x, y, bar_width, 1, false, BackgroundFlag::Screen);
+    }
 
-        let new_room = Rect::new(x, y, w, h);
+    panel.set_default_foreground(WHITE);
+    panel.print_ex(
+        x + total_width / 2,
+        y,
+        BackgroundFlag::None,
+        TextAlignment::Center,
+        format!("{}: {}/{}", name, value, maximum),
+    );
+}
 
-        // 判断所有已存在的房间是否和新创建的房间相交
-        let failed = rooms
-            .iter()
-            .any(|other_room| new_room.intersects_with(other_room));
+/// 鼠标所在位置、并且在视野内的所有对象的名字
+fn get_names_under_mouse(mouse: Mouse, objects: &[Object], fov_map: &FovMap) -> String {
+    let (x, y) = (mouse.cx as i32, mouse.cy as i32);
 
-        if !failed {
-            // 有效房间，绘制在地图上
-            create_room(new_room, &mut map);
-            // 创建怪物
-            place_objects(new_room, objects);
+    let names = objects
+        .iter()
+        .filter(|obj| obj.pos() == (x, y) && fov_map.is_in_fov(obj.x, obj.y))
+        .map(|obj| obj.name.clone())
+        .collect::<Vec<_>>();
 
-            let (new_x, new_y) = new_room.center();
+    names.join(", ")
+}
 
-            if rooms.is_empty() {
-                // 玩家从第一个房间开始
-                objects[PLAYER].set_pos(new_x, new_y);
-            } else {
-                // 我们可以从一个水平隧道开始，到达与新房间相同的高度，然后与一个垂直隧道相连，或者我们可以做相反的事情:从一个垂直隧道开始，以一个水平隧道结束。
+/// 设置颜色然后再当前位置绘制对象的字符
+// &mut dyn Console 这里的限定表示 con 只要实现 Console trait 即可。
+// 这种限定方法叫 trait object
+fn draw_object(object: &Object, con: &mut dyn Console) {
+    con.set_default_foreground(object.color.into());
+    con.put_char(object.x, object.y, object.char, BackgroundFlag::None);
+}
 
-                // 前一个房间的中心点
-                let (prev_x, prev_y) = rooms[rooms.len() - 1].center();
+fn handle_keys(
+    key: Key,
+    tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &mut Vec<Object>,
+    templates: &Templates,
+) -> PlayerAction {
+    use PlayerAction::*;
 
-                // 随机 true 和 false 对应两种不同的通道方式
-                if rand::random() {
-                    create_h_tunnel(prev_x, new_x, prev_y, &mut map);
-                    create_v_tunnel(prev_y, new_y, new_x, &mut map);
-                } else {
-                    create_v_tunnel(prev_y, new_y, prev_x, &mut map);
-                    create_h_tunnel(prev_x, new_x, new_y, &mut map);
+    match key {
+        Key { code: Up, .. } => TookTurn(player_move_or_attack(0, -1, game, objects)),
+        Key { code: Down, .. } => TookTurn(player_move_or_attack(0, 1, game, objects)),
+        Key { code: Left, .. } => TookTurn(player_move_or_attack(-1, 0, game, objects)),
+        Key { code: Right, .. } => TookTurn(player_move_or_attack(1, 0, game, objects)),
+        Key {
+            code: Enter,
+            alt: true,
+            ..
+        } => {
+            // 切换全屏不消耗回合
+            let fullscreen = tcod.root.is_fullscreen();
+            tcod.root.set_fullscreen(!fullscreen);
+            DidntTakeTurn
+        }
+        // Shift+'.' 按下时 printable 仍是 '.'，'>' 只出现在随后的文本事件里
+        Key { code: Text, .. } if key.text() == ">" && player_on_stairs(game, objects) => {
+            // 下楼，换了新地图需要重建 FOV
+            next_level(game, objects, templates);
+            initialise_fov(tcod, &game.map);
+            recompute_fov(tcod, objects);
+            DidntTakeTurn
+        }
+        Key { printable: 'g', .. } => {
+            // 拾取脚下的物品
+            match item_under_player(objects) {
+                Some(item_id) => {
+                    pick_item_up(item_id, game, objects);
+                    TookTurn(1)
                 }
+                None => DidntTakeTurn,
             }
-
-            rooms.push(new_room);
         }
-    }
-
-    map
-}
-
-fn place_objects(room: Rect, objects: &mut Vec<Object>) {
-    let num_monsters = rand::thread_rng().gen_range(0..MAX_ROOM_MONSTERS + 1);
-
-    for _ in 0..num_monsters {
-        let x = rand::thread_rng().gen_range(room.x1 + 1..room.x2);
-        let y = rand::thread_rng().gen_range(room.y1..room.y2);
-        let monster = if rand::random::<f32>() < 0.8 {
-            // 80%的几率是兽人
-            Object::new(x, y, 'o', "orc", DESATURATED_GREEN, true)
-        } else {
-            Object::new(x, y, 'T', "troll", DARKER_GREEN, true)
-        };
-        objects.push(monster);
-    }
-}
-
-/// 将一个矩形放置在图上，并确保其地图快是空的
-fn create_room(room: Rect, map: &mut Map) {
-    for x in (room.x1 + 1)..room.x2 {
-        for y in (room.y1 + 1)..room.y2 {
-            map[x as usize][y as usize] = Tile::empty();
+        Key { printable: 'c', .. } => {
+            // 关上身边开着的门
+            if close_door(game, objects) {
+                TookTurn(1)
+            } else {
+                DidntTakeTurn
+            }
         }
+        Key { code: Escape, .. } => Exit,
+        _ => DidntTakeTurn,
     }
 }
 
-// 创建水平隧道
-fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut Map) {
-    // `min()` 和 `max()` 用于 `x1 > x2` 的情况
-    // 确保..能有正确的值返回
-    for x in cmp::min(x1, x2)..(cmp::max(x1, x2) + 1) {
-        map[x as usize][y as usize] = Tile::empty();
-    }
-}
-
-// 垂直隧道
-fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
-    for y in cmp::min(y1, y2)..(cmp::max(y1, y2) + 1) {
-        map[x as usize][y as usize] = Tile::empty();
+/// 从命令行读取 `<flag> <值>`，值无法解析时打印 `expected` 并退出
+fn arg_from_args<T: FromStr>(flag: &str, expected: &str) -> Option<T> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            let value = args.next().unwrap_or_default();
+            return Some(value.parse().unwrap_or_else(|_| {
+                eprintln!("{} expects {}, got {:?}", flag, expected, value);
+                std::process::exit(2);
+            }));
+        }
     }
+    None
 }
@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color::{Color, WHITE};
+
+// 地图大小
+pub const MAP_WIDTH: i32 = 80;
+pub const MAP_HEIGHT: i32 = 45;
+
+pub type Map = Vec<Vec<Tile>>;
+
+/// 地图的瓦片和它的属性
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Tile {
+    /// 地形种类，决定外观和能否通过
+    pub kind: TileKind,
+    /// 战争迷雾
+    pub explored: bool,
+}
+
+/// 门的状态
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Door {
+    Open,
+    Closed,
+}
+
+/// 地形种类，每种地形的外观和规则都在 `TileKind::info` 中定义
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TileKind {
+    Wall,
+    Floor,
+    Door(Door),
+    Water,
+    Lava,
+    Grass,
+    Rubble,
+    Stairs,
+}
+
+/// 一种地形的字符、颜色和通行规则
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileInfo {
+    /// 画在瓦片上的字符，空格表示只画背景
+    pub glyph: char,
+    /// 在视野内的前景色和背景色
+    pub light_fg: Color,
+    pub light_bg: Color,
+    /// 已探索但在视野外的前景色和背景色
+    pub dark_fg: Color,
+    pub dark_bg: Color,
+    /// 走进这一格花费的回合数，`None` 表示无法通过
+    pub move_cost: Option<u32>,
+    /// 阻挡视线
+    pub block_sight: bool,
+}
+
+// 墙和地面的背景色，其它地形在它们的基础上画字符
+const DARK_WALL: Color = Color::new(0, 0, 100);
+const LIGHT_WALL: Color = Color::new(130, 110, 50);
+const DARK_GROUND: Color = Color::new(50, 50, 150);
+const LIGHT_GROUND: Color = Color::new(200, 180, 50);
+const LIGHT_DOOR: Color = Color::new(191, 95, 0);
+const DARK_DOOR: Color = Color::new(95, 47, 0);
+
+impl TileKind {
+    /// 这种地形的外观和规则，新增地形只需要在这里加一项
+    pub fn info(self) -> TileInfo {
+        // 画在地面上的地形
+        let on_ground = |glyph, light_fg, dark_fg, move_cost| TileInfo {
+            glyph,
+            light_fg,
+            light_bg: LIGHT_GROUND,
+            dark_fg,
+            dark_bg: DARK_GROUND,
+            move_cost,
+            block_sight: false,
+        };
+        match self {
+            TileKind::Wall => TileInfo {
+                glyph: ' ',
+                light_fg: LIGHT_WALL,
+                light_bg: LIGHT_WALL,
+                dark_fg: DARK_WALL,
+                dark_bg: DARK_WALL,
+                move_cost: None,
+                block_sight: true,
+            },
+            TileKind::Floor => on_ground(' ', LIGHT_GROUND, DARK_GROUND, Some(1)),
+            TileKind::Door(Door::Closed) => TileInfo {
+                glyph: '+',
+                light_fg: LIGHT_DOOR,
+                light_bg: LIGHT_WALL,
+                dark_fg: DARK_DOOR,
+                dark_bg: DARK_WALL,
+                move_cost: None,
+                block_sight: true,
+            },
+            TileKind::Door(Door::Open) => on_ground('\'', LIGHT_DOOR, DARK_DOOR, Some(1)),
+            TileKind::Water => TileInfo {
+                glyph: '~',
+                light_fg: Color::new(127, 191, 255),
+                light_bg: Color::new(0, 63, 191),
+                dark_fg: Color::new(63, 63, 159),
+                dark_bg: Color::new(0, 0, 127),
+                move_cost: Some(2),
+                block_sight: false,
+            },
+            // 岩浆不能踩，但看得过去
+            TileKind::Lava => TileInfo {
+                glyph: '~',
+                light_fg: Color::new(255, 191, 0),
+                light_bg: Color::new(191, 31, 0),
+                dark_fg: Color::new(127, 47, 0),
+                dark_bg: Color::new(63, 0, 31),
+                move_cost: None,
+                block_sight: false,
+            },
+            TileKind::Grass => {
+                on_ground('"', Color::new(0, 127, 0), Color::new(31, 63, 95), Some(1))
+            }
+            TileKind::Rubble => {
+                on_ground(':', Color::new(95, 79, 47), Color::new(31, 31, 79), Some(2))
+            }
+            TileKind::Stairs => on_ground('>', WHITE, Color::new(159, 159, 191), Some(1)),
+        }
+    }
+}
+
+impl Tile {
+    pub fn new(kind: TileKind) -> Self {
+        Self {
+            kind,
+            explored: false,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Tile::new(TileKind::Floor)
+    }
+
+    pub fn wall() -> Self {
+        Tile::new(TileKind::Wall)
+    }
+
+    /// 关着的门
+    pub fn door() -> Self {
+        Tile::new(TileKind::Door(Door::Closed))
+    }
+
+    /// 该块是否被阻挡无法移动到此处
+    pub fn blocked(&self) -> bool {
+        self.kind.info().move_cost.is_none()
+    }
+
+    /// 是否阻挡视线
+    pub fn block_sight(&self) -> bool {
+        self.kind.info().block_sight
+    }
+
+    /// 门的状态，不是门时为 `None`
+    pub fn door_state(&self) -> Option<Door> {
+        match self.kind {
+            TileKind::Door(door) => Some(door),
+            _ => None,
+        }
+    }
+
+    /// 打开或关上门，不是门时什么都不做
+    pub fn set_door(&mut self, door: Door) {
+        if self.door_state().is_some() {
+            self.kind = TileKind::Door(door);
+        }
+    }
+
+    /// 是否可以走过去，关着的门打开后就能通过
+    pub fn passable(&self) -> bool {
+        !self.blocked() || self.door_state().is_some()
+    }
+}
+
+/// 一个在地图上的矩形，用于表示房间
+#[derive(Clone, Copy, Debug)]
+pub struct Rect {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
+}
+
+impl Rect {
+    /// 创建一个矩形
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Self {
+            x1: x,
+            y1: y,
+            x2: x + w,
+            y2: y + h,
+        }
+    }
+
+    /// 获取中心点
+    pub fn center(&self) -> (i32, i32) {
+        let center_x = (self.x1 + self.x2) / 2;
+        let center_y = (self.y1 + self.y2) / 2;
+        (center_x, center_y)
+    }
+
+    /// 如果一个图形与另一个图形相交返回 true
+    pub fn intersects_with(&self, other: &Rect) -> bool {
+        (self.x1 <= other.x2)
+            && (self.x2 >= other.x1)
+            && (self.y1 <= other.y2)
+            && (self.y2 >= other.y1)
+    }
+}
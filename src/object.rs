@@ -0,0 +1,315 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::ai::Ai;
+use crate::color::{Color, DARK_RED, LIGHT_GREEN, LIGHT_YELLOW, ORANGE, RED, WHITE};
+use crate::game::Game;
+use crate::messages::Messages;
+
+// 玩家是第一位
+pub const PLAYER: usize = 0;
+
+/// 这是一个通用对象的抽：玩家、怪物、物品、楼梯等
+/// 它始终由屏幕上的字符表示
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Object {
+    pub x: i32,
+    pub y: i32,
+    pub char: char,
+    pub color: Color,
+    pub name: String,
+    pub blocks: bool,
+    pub alive: bool,
+    /// 角色等级，只对玩家有意义
+    pub level: i32,
+    pub fighter: Option<Fighter>,
+    pub ai: Option<Box<dyn Ai>>,
+    pub item: Option<Item>,
+    pub equipment: Option<Equipment>,
+}
+
+/// 可以拾取放进背包的物品
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Item {
+    Heal,
+    Lightning,
+    Confuse,
+    Fireball,
+    /// 使用时装备或卸下，属性在 `Object::equipment` 中
+    Equipment,
+}
+
+/// 可以装备的物品，装备后为玩家提供属性加成
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub slot: Slot,
+    pub equipped: bool,
+    pub max_hp_bonus: i32,
+    pub power_bonus: i32,
+    pub defense_bonus: i32,
+}
+
+/// 装备栏位，每个栏位同时只能装备一件
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Slot {
+    RightHand,
+    LeftHand,
+    Body,
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Slot::RightHand => write!(f, "right hand"),
+            Slot::LeftHand => write!(f, "left hand"),
+            Slot::Body => write!(f, "body"),
+        }
+    }
+}
+
+/// 战斗相关的属性和方法（玩家、怪物）
+///
+/// `base_*` 是不含装备加成的数值，实际数值用 `Object::max_hp` 等方法获取
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fighter {
+    pub base_max_hp: i32,
+    pub hp: i32,
+    pub base_defense: i32,
+    pub base_power: i32,
+    /// 玩家累计的经验值；怪物的是被杀死时奖励给玩家的经验值
+    pub xp: i32,
+    pub on_death: DeathCallback,
+}
+
+/// 对象死亡时调用的回调
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeathCallback {
+    Player,
+    Monster,
+}
+
+impl DeathCallback {
+    fn callback(self, object: &mut Object, game: &mut Game) {
+        use DeathCallback::*;
+        let callback: fn(&mut Object, &mut Game) = match self {
+            Player => player_death,
+            Monster => monster_death,
+        };
+        callback(object, game);
+    }
+}
+
+impl Object {
+    /// 快捷方法创建一个对象
+    pub fn new(x: i32, y: i32, char: char, name: &str, color: Color, blocks: bool) -> Self {
+        Self {
+            x,
+            y,
+            char,
+            color,
+            name: name.into(),
+            blocks,
+            alive: false,
+            level: 1,
+            fighter: None,
+            ai: None,
+            item: None,
+            equipment: None,
+        }
+    }
+
+    /// 获取对象位置
+    pub fn pos(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    /// 设置对象位置
+    pub fn set_pos(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    /// 到另一个对象的距离
+    pub fn distance_to(&self, other: &Object) -> f32 {
+        let dx = other.x - self.x;
+        let dy = other.y - self.y;
+        ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
+    }
+
+    /// 到某个位置的距离
+    pub fn distance(&self, x: i32, y: i32) -> f32 {
+        (((x - self.x).pow(2) + (y - self.y).pow(2)) as f32).sqrt()
+    }
+
+    /// 受到伤害，HP 降到 0 时触发死亡回调
+    ///
+    /// 如果因此死亡，返回击杀者获得的经验值
+    pub fn take_damage(&mut self, damage: i32, game: &mut Game) -> Option<i32> {
+        if let Some(fighter) = self.fighter.as_mut() {
+            if damage > 0 {
+                fighter.hp -= damage;
+            }
+        }
+        // 检查是否死亡
+        if let Some(fighter) = self.fighter {
+            if fighter.hp <= 0 {
+                self.alive = false;
+                fighter.on_death.callback(self, game);
+                return Some(fighter.xp);
+            }
+        }
+        None
+    }
+
+    /// 恢复 HP，不超过最大值
+    pub fn heal(&mut self, amount: i32, game: &Game) {
+        let max_hp = self.max_hp(game);
+        if let Some(ref mut fighter) = self.fighter {
+            fighter.hp += amount;
+            if fighter.hp > max_hp {
+                fighter.hp = max_hp;
+            }
+        }
+    }
+
+    /// 当前 HP 不超过最大值，卸下加 HP 的装备后需要调用
+    pub fn clamp_hp(&mut self, game: &Game) {
+        let max_hp = self.max_hp(game);
+        if let Some(ref mut fighter) = self.fighter {
+            fighter.hp = fighter.hp.min(max_hp);
+        }
+    }
+
+    /// 攻击力：基础值加上所有装备的加成
+    pub fn power(&self, game: &Game) -> i32 {
+        let base_power = self.fighter.map_or(0, |f| f.base_power);
+        let bonus: i32 = self
+            .get_all_equipped(game)
+            .iter()
+            .map(|e| e.power_bonus)
+            .sum();
+        base_power + bonus
+    }
+
+    /// 防御：基础值加上所有装备的加成
+    pub fn defense(&self, game: &Game) -> i32 {
+        let base_defense = self.fighter.map_or(0, |f| f.base_defense);
+        let bonus: i32 = self
+            .get_all_equipped(game)
+            .iter()
+            .map(|e| e.defense_bonus)
+            .sum();
+        base_defense + bonus
+    }
+
+    /// 最大 HP：基础值加上所有装备的加成
+    pub fn max_hp(&self, game: &Game) -> i32 {
+        let base_max_hp = self.fighter.map_or(0, |f| f.base_max_hp);
+        let bonus: i32 = self
+            .get_all_equipped(game)
+            .iter()
+            .map(|e| e.max_hp_bonus)
+            .sum();
+        base_max_hp + bonus
+    }
+
+    /// 当前装备着的所有物品，只有玩家有背包
+    pub fn get_all_equipped(&self, game: &Game) -> Vec<Equipment> {
+        if self
+            .fighter
+            .is_some_and(|f| f.on_death == DeathCallback::Player)
+        {
+            game.inventory
+                .iter()
+                .filter_map(|item| item.equipment)
+                .filter(|equipment| equipment.equipped)
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    /// 装备这个物品
+    pub fn equip(&mut self, messages: &mut Messages) {
+        if let Some(ref mut equipment) = self.equipment {
+            if !equipment.equipped {
+                equipment.equipped = true;
+                messages.add(
+                    format!("Equipped {} on {}.", self.name, equipment.slot),
+                    LIGHT_GREEN,
+                );
+            }
+        } else {
+            messages.add(
+                format!("Can't equip {} because it's not an Equipment.", self.name),
+                RED,
+            );
+        }
+    }
+
+    /// 卸下这个物品
+    pub fn dequip(&mut self, messages: &mut Messages) {
+        if let Some(ref mut equipment) = self.equipment {
+            if equipment.equipped {
+                equipment.equipped = false;
+                messages.add(
+                    format!("Dequipped {} from {}.", self.name, equipment.slot),
+                    LIGHT_YELLOW,
+                );
+            }
+        } else {
+            messages.add(
+                format!("Can't dequip {} because it's not an Equipment.", self.name),
+                RED,
+            );
+        }
+    }
+
+    /// 攻击目标，伤害 = 攻击力 - 目标防御
+    pub fn attack(&mut self, target: &mut Object, game: &mut Game) {
+        let damage = self.power(game) - target.defense(game);
+        if damage > 0 {
+            game.messages.add(
+                format!(
+                    "{} attacks {} for {} hit points.",
+                    self.name, target.name, damage
+                ),
+                WHITE,
+            );
+            if let Some(xp) = target.take_damage(damage, game) {
+                // 击杀奖励经验值
+                if let Some(fighter) = self.fighter.as_mut() {
+                    fighter.xp += xp;
+                }
+            }
+        } else {
+            game.messages.add(
+                format!(
+                    "{} attacks {} but it has no effect!",
+                    self.name, target.name
+                ),
+                WHITE,
+            );
+        }
+    }
+}
+
+fn player_death(player: &mut Object, game: &mut Game) {
+    // 游戏结束
+    game.messages.add("You died!", RED);
+
+    player.char = '%';
+    player.color = DARK_RED;
+}
+
+fn monster_death(monster: &mut Object, game: &mut Game) {
+    // 变成一具尸体，不再阻挡，也不能被攻击
+    game.messages
+        .add(format!("{} is dead!", monster.name), ORANGE);
+    monster.char = '%';
+    monster.color = DARK_RED;
+    monster.blocks = false;
+    monster.fighter = None;
+    monster.ai = None;
+    monster.name = format!("remains of {}", monster.name);
+}
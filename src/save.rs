@@ -0,0 +1,110 @@
+//! 存档：把 `Game`（包括每个瓦片的 `explored`）和所有对象写成带版本号的 JSON
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::game::Game;
+use crate::object::Object;
+
+/// 存档格式的版本，存档结构发生不兼容的变化时加一
+pub const SAVE_VERSION: u32 = 9;
+
+#[derive(Serialize)]
+struct SaveFile<'a> {
+    version: u32,
+    game: &'a Game,
+    objects: &'a [Object],
+}
+
+#[derive(Deserialize)]
+struct LoadedSave {
+    game: Game,
+    objects: Vec<Object>,
+}
+
+/// 只读取版本号，用于在解析完整内容前判断是否兼容
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+/// 读写存档时可能出现的错误
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    /// 文件内容不是有效的存档
+    Corrupt(serde_json::Error),
+    /// 存档来自不兼容的版本
+    IncompatibleVersion {
+        found: u32,
+        expected: u32,
+    },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "could not access the save file: {}", err),
+            SaveError::Corrupt(err) => write!(f, "the save file is corrupt: {}", err),
+            SaveError::IncompatibleVersion { found, expected } => write!(
+                f,
+                "the save file has version {}, but this game reads version {}",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl Error for SaveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SaveError::Io(err) => Some(err),
+            SaveError::Corrupt(err) => Some(err),
+            SaveError::IncompatibleVersion { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(err: serde_json::Error) -> Self {
+        SaveError::Corrupt(err)
+    }
+}
+
+/// 保存游戏到指定路径
+pub fn save_game(path: impl AsRef<Path>, game: &Game, objects: &[Object]) -> Result<(), SaveError> {
+    let save = SaveFile {
+        version: SAVE_VERSION,
+        game,
+        objects,
+    };
+    let data = serde_json::to_string(&save)?;
+    fs::write(path, data)?;
+    Ok(())
+}
+
+/// 从指定路径读取存档
+pub fn load_game(path: impl AsRef<Path>) -> Result<(Game, Vec<Object>), SaveError> {
+    let data = fs::read_to_string(path)?;
+
+    let header: SaveHeader = serde_json::from_str(&data)?;
+    if header.version != SAVE_VERSION {
+        return Err(SaveError::IncompatibleVersion {
+            found: header.version,
+            expected: SAVE_VERSION,
+        });
+    }
+
+    let save: LoadedSave = serde_json::from_str(&data)?;
+    Ok((save.game, save.objects))
+}
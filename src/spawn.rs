@@ -0,0 +1,73 @@
+//! 随层数变化的出现权重，以及按权重随机选择的出现表
+
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use serde::Deserialize;
+
+/// 从第 `level` 层开始，数值变为 `value`
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transition {
+    pub level: u32,
+    pub value: u32,
+}
+
+/// 第 `level` 层的数值：最后一个不超过该层的 `Transition`，比第一项还浅时为 0
+pub fn from_dungeon_level(table: &[Transition], level: u32) -> u32 {
+    table
+        .iter()
+        .rev()
+        .find(|transition| level >= transition.level)
+        .map_or(0, |transition| transition.value)
+}
+
+/// 检查表格：不能为空，层数至少为 1 且严格递增。第一项不必是第 1 层，更浅的层数值为 0
+pub fn check_table(table: &[Transition]) -> Result<(), String> {
+    if table.is_empty() {
+        return Err("the table needs at least one entry".into());
+    }
+    let mut previous = 0;
+    for transition in table {
+        if transition.level == 0 {
+            return Err("levels are counted from 1, got 0".into());
+        }
+        if transition.level <= previous {
+            return Err(format!(
+                "levels must increase, got {} after {}",
+                transition.level, previous
+            ));
+        }
+        previous = transition.level;
+    }
+    Ok(())
+}
+
+/// 某一层的出现表，每一项带有该层的权重
+pub struct SpawnTable<T> {
+    entries: Vec<(T, u32)>,
+    /// 权重全为 0 时为空
+    index: Option<WeightedIndex<u32>>,
+}
+
+impl<T> SpawnTable<T> {
+    pub fn new(entries: Vec<(T, u32)>) -> Self {
+        let index = WeightedIndex::new(entries.iter().map(|&(_, weight)| weight)).ok();
+        SpawnTable { entries, index }
+    }
+
+    /// 按权重随机选择一项，没有可选的项时返回 `None`
+    pub fn choose(&self, rng: &mut impl Rng) -> Option<&T> {
+        let index = self.index.as_ref()?;
+        Some(&self.entries[index.sample(rng)].0)
+    }
+
+    /// 每一项被选中的概率，权重为 0 的项不列出
+    pub fn probabilities(&self) -> Vec<(&T, f32)> {
+        let total: u32 = self.entries.iter().map(|&(_, weight)| weight).sum();
+        self.entries
+            .iter()
+            .filter(|&&(_, weight)| weight > 0)
+            .map(|(entry, weight)| (entry, *weight as f32 / total as f32))
+            .collect()
+    }
+}
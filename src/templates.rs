@@ -0,0 +1,596 @@
+//! 怪物和物品的模板，从数据文件读取，添加内容不需要重新编译
+//!
+//! 数据文件是 JSON，格式见 `data/templates.json`
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::ai::{Ai, BasicAi};
+use crate::color::Color;
+use crate::map::{Door, Tile, TileKind};
+use crate::object::{DeathCallback, Equipment, Fighter, Item, Object, Slot};
+use crate::spawn::{check_table, from_dungeon_level, SpawnTable, Transition};
+
+/// 数据文件中的所有模板
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Templates {
+    /// 每个房间最多的怪物数量，随层数变化
+    pub max_room_monsters: Vec<Transition>,
+    /// 每个房间最多的物品数量，随层数变化
+    pub max_room_items: Vec<Transition>,
+    pub monsters: Vec<MonsterTemplate>,
+    pub items: Vec<ItemTemplate>,
+    /// 手工设计的预制房间，可以省略
+    #[serde(default)]
+    pub vaults: Vec<VaultTemplate>,
+}
+
+/// 一种怪物
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonsterTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub color: Color,
+    /// 第一层的属性，越深的怪物越强壮
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+    /// 被杀死时奖励给玩家的经验值
+    pub xp: i32,
+    pub ai: AiKind,
+    /// 与同一层其它怪物相比的出现权重，随层数变化
+    pub spawn_weight: Vec<Transition>,
+    /// 最早出现的层数，从 1 开始
+    pub min_depth: u32,
+    /// 最晚出现的层数，为空时不限制
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+}
+
+/// 怪物使用的 AI
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum AiKind {
+    /// 看见玩家就追上去攻击
+    Basic,
+}
+
+/// 一种物品
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub color: Color,
+    pub item: Item,
+    /// `item` 为 `Equipment` 时必须提供
+    #[serde(default)]
+    pub equipment: Option<EquipmentTemplate>,
+    pub spawn_weight: Vec<Transition>,
+    pub min_depth: u32,
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+}
+
+/// 装备的栏位和加成，省略的加成为 0
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EquipmentTemplate {
+    pub slot: Slot,
+    #[serde(default)]
+    pub max_hp_bonus: i32,
+    #[serde(default)]
+    pub power_bonus: i32,
+    #[serde(default)]
+    pub defense_bonus: i32,
+}
+
+/// 用 ASCII 字符画设计的预制房间，生成地图时放进足够大的房间里
+///
+/// `layout` 中每行一样长，字符的含义：
+///
+/// - `#` 墙
+/// - `.` 地面
+/// - `+` 关着的门
+/// - `~` 水，`=` 岩浆，`"` 草地，`:` 碎石
+/// - `m` 地面，放一只按这一层出现表随机选择的怪物
+/// - `i` 地面，放一件按这一层出现表随机选择的物品
+/// - `legend` 中的字符：地面，放指定名字的怪物或物品
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VaultTemplate {
+    pub name: String,
+    pub layout: Vec<String>,
+    #[serde(default)]
+    pub legend: BTreeMap<char, VaultMarker>,
+    pub min_depth: u32,
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+}
+
+/// 预制房间中放置固定怪物或物品的标记
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VaultMarker {
+    /// 怪物模板的名字
+    Monster(String),
+    /// 物品模板的名字
+    Item(String),
+}
+
+// 预制房间中有固定含义、不能在 legend 里重新定义的字符
+pub const VAULT_WALL: char = '#';
+pub const VAULT_FLOOR: char = '.';
+pub const VAULT_DOOR: char = '+';
+pub const VAULT_MONSTER: char = 'm';
+pub const VAULT_ITEM: char = 'i';
+pub const VAULT_WATER: char = '~';
+pub const VAULT_LAVA: char = '=';
+pub const VAULT_GRASS: char = '"';
+pub const VAULT_RUBBLE: char = ':';
+const VAULT_RESERVED: [char; 9] = [
+    VAULT_WALL,
+    VAULT_FLOOR,
+    VAULT_DOOR,
+    VAULT_MONSTER,
+    VAULT_ITEM,
+    VAULT_WATER,
+    VAULT_LAVA,
+    VAULT_GRASS,
+    VAULT_RUBBLE,
+];
+
+/// 预制房间中的字符对应的地形，怪物、物品和 legend 中的标记下面都是地面
+pub fn vault_terrain(glyph: char) -> TileKind {
+    match glyph {
+        VAULT_WALL => TileKind::Wall,
+        VAULT_DOOR => TileKind::Door(Door::Closed),
+        VAULT_WATER => TileKind::Water,
+        VAULT_LAVA => TileKind::Lava,
+        VAULT_GRASS => TileKind::Grass,
+        VAULT_RUBBLE => TileKind::Rubble,
+        _ => TileKind::Floor,
+    }
+}
+
+/// 读取模板时可能出现的错误
+#[derive(Debug)]
+pub enum TemplateError {
+    Io(io::Error),
+    /// 文件不是有效的 JSON，或者字段不对；错误信息里有行号和列号
+    Parse(serde_json::Error),
+    /// 某个模板的内容不合理
+    Invalid {
+        /// `"monsters"`、`"items"` 或 `"vaults"`
+        section: &'static str,
+        /// 在该列表中的序号，从 0 开始
+        index: usize,
+        name: String,
+        reason: String,
+    },
+    /// 每个房间数量的表格不合理
+    InvalidTable {
+        field: &'static str,
+        reason: String,
+    },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Io(err) => write!(f, "could not read the templates: {}", err),
+            TemplateError::Parse(err) => write!(f, "the templates are malformed: {}", err),
+            TemplateError::Invalid {
+                section,
+                index,
+                name,
+                reason,
+            } => write!(f, "{}[{}] ({:?}): {}", section, index, name, reason),
+            TemplateError::InvalidTable { field, reason } => write!(f, "{}: {}", field, reason),
+        }
+    }
+}
+
+impl Error for TemplateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TemplateError::Io(err) => Some(err),
+            TemplateError::Parse(err) => Some(err),
+            TemplateError::Invalid { .. } | TemplateError::InvalidTable { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for TemplateError {
+    fn from(err: io::Error) -> Self {
+        TemplateError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for TemplateError {
+    fn from(err: serde_json::Error) -> Self {
+        TemplateError::Parse(err)
+    }
+}
+
+impl Templates {
+    /// 读取并检查数据文件
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Templates, TemplateError> {
+        let text = fs::read_to_string(path)?;
+        Templates::from_json(&text)
+    }
+
+    /// 解析并检查 JSON 格式的模板
+    pub fn from_json(text: &str) -> Result<Templates, TemplateError> {
+        let templates: Templates = serde_json::from_str(text)?;
+        templates.validate()?;
+        Ok(templates)
+    }
+
+    /// 检查每个模板，返回第一个有问题的条目
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        for (field, table) in [
+            ("max_room_monsters", &self.max_room_monsters),
+            ("max_room_items", &self.max_room_items),
+        ] {
+            check_table(table).map_err(|reason| TemplateError::InvalidTable { field, reason })?;
+        }
+
+        let mut names = HashSet::new();
+        for (index, monster) in self.monsters.iter().enumerate() {
+            let invalid = |reason: String| TemplateError::Invalid {
+                section: "monsters",
+                index,
+                name: monster.name.clone(),
+                reason,
+            };
+            check_common(
+                &monster.name,
+                monster.glyph,
+                monster.min_depth,
+                monster.max_depth,
+                &monster.spawn_weight,
+            )
+            .map_err(invalid)?;
+            if !names.insert(monster.name.as_str()) {
+                return Err(invalid("the name is used by another monster".into()));
+            }
+            if monster.hp <= 0 {
+                return Err(invalid(format!("hp must be positive, got {}", monster.hp)));
+            }
+            if monster.defense < 0 || monster.power < 0 || monster.xp < 0 {
+                return Err(invalid("defense, power and xp cannot be negative".into()));
+            }
+        }
+
+        let mut names = HashSet::new();
+        for (index, item) in self.items.iter().enumerate() {
+            let invalid = |reason: String| TemplateError::Invalid {
+                section: "items",
+                index,
+                name: item.name.clone(),
+                reason,
+            };
+            check_common(
+                &item.name,
+                item.glyph,
+                item.min_depth,
+                item.max_depth,
+                &item.spawn_weight,
+            )
+            .map_err(invalid)?;
+            if !names.insert(item.name.as_str()) {
+                return Err(invalid("the name is used by another item".into()));
+            }
+            match (item.item, item.equipment) {
+                (Item::Equipment, None) => {
+                    return Err(invalid(
+                        "equipment items need an \"equipment\" entry".into(),
+                    ))
+                }
+                (Item::Equipment, Some(_)) | (_, None) => {}
+                (_, Some(_)) => {
+                    return Err(invalid(format!(
+                        "only \"Equipment\" items can have an \"equipment\" entry, not {:?}",
+                        item.item
+                    )))
+                }
+            }
+        }
+
+        for (index, vault) in self.vaults.iter().enumerate() {
+            self.check_vault(vault)
+                .map_err(|reason| TemplateError::Invalid {
+                    section: "vaults",
+                    index,
+                    name: vault.name.clone(),
+                    reason,
+                })?;
+        }
+        Ok(())
+    }
+
+    /// 检查预制房间的形状、字符和引用的模板
+    fn check_vault(&self, vault: &VaultTemplate) -> Result<(), String> {
+        if vault.name.trim().is_empty() {
+            return Err("the name cannot be empty".into());
+        }
+        check_depth(vault.min_depth, vault.max_depth)?;
+        let width = vault.width();
+        if width == 0 {
+            return Err("the layout cannot be empty".into());
+        }
+        if let Some(row) = vault
+            .layout
+            .iter()
+            .position(|line| line.chars().count() != width)
+        {
+            return Err(format!(
+                "row {} is {} characters wide, the first row is {}",
+                row,
+                vault.layout[row].chars().count(),
+                width
+            ));
+        }
+
+        for (&glyph, marker) in &vault.legend {
+            if VAULT_RESERVED.contains(&glyph) {
+                return Err(format!("the legend cannot redefine {:?}", glyph));
+            }
+            match marker {
+                VaultMarker::Monster(name) if self.monster(name).is_none() => {
+                    return Err(format!(
+                        "{:?} refers to an unknown monster {:?}",
+                        glyph, name
+                    ))
+                }
+                VaultMarker::Item(name) if self.item(name).is_none() => {
+                    return Err(format!("{:?} refers to an unknown item {:?}", glyph, name))
+                }
+                _ => {}
+            }
+        }
+        if let Some((x, y, glyph)) = vault.cells().find(|(_, _, glyph)| {
+            !VAULT_RESERVED.contains(glyph) && !vault.legend.contains_key(glyph)
+        }) {
+            return Err(format!(
+                "unknown character {:?} at row {}, column {}",
+                glyph, y, x
+            ));
+        }
+
+        // 房间外面一圈是地面，所有地面都要能从边上走进来
+        match vault.unreachable_tile() {
+            Some((x, y)) => Err(format!(
+                "the tile at row {}, column {} cannot be reached from outside",
+                y, x
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// 按名字查找怪物模板
+    pub fn monster(&self, name: &str) -> Option<&MonsterTemplate> {
+        self.monsters.iter().find(|monster| monster.name == name)
+    }
+
+    /// 按名字查找物品模板
+    pub fn item(&self, name: &str) -> Option<&ItemTemplate> {
+        self.items.iter().find(|item| item.name == name)
+    }
+
+    /// 第 `level` 层每个房间最多的怪物数量
+    pub fn max_room_monsters(&self, level: u32) -> u32 {
+        from_dungeon_level(&self.max_room_monsters, level)
+    }
+
+    /// 第 `level` 层每个房间最多的物品数量
+    pub fn max_room_items(&self, level: u32) -> u32 {
+        from_dungeon_level(&self.max_room_items, level)
+    }
+
+    /// 第 `level` 层的怪物出现表
+    pub fn monster_table(&self, level: u32) -> SpawnTable<&MonsterTemplate> {
+        SpawnTable::new(
+            self.monsters
+                .iter()
+                .map(|monster| (monster, monster.weight_at(level)))
+                .collect(),
+        )
+    }
+
+    /// 第 `level` 层的物品出现表
+    pub fn item_table(&self, level: u32) -> SpawnTable<&ItemTemplate> {
+        SpawnTable::new(
+            self.items
+                .iter()
+                .map(|item| (item, item.weight_at(level)))
+                .collect(),
+        )
+    }
+
+    /// 列出第 `level` 层每种怪物和物品实际出现的概率，用来调整平衡
+    pub fn describe_spawns(&self, level: u32) -> String {
+        let mut text = format!(
+            "Dungeon level {}\nUp to {} monsters and {} items per room\n",
+            level,
+            self.max_room_monsters(level),
+            self.max_room_items(level)
+        );
+        text.push_str("Monsters:\n");
+        for (monster, chance) in self.monster_table(level).probabilities() {
+            text.push_str(&format!("{:6.1}%  {}\n", chance * 100.0, monster.name));
+        }
+        text.push_str("Items:\n");
+        for (item, chance) in self.item_table(level).probabilities() {
+            text.push_str(&format!("{:6.1}%  {}\n", chance * 100.0, item.name));
+        }
+        text
+    }
+}
+
+/// 怪物和物品都需要满足的条件
+fn check_common(
+    name: &str,
+    glyph: char,
+    min_depth: u32,
+    max_depth: Option<u32>,
+    spawn_weight: &[Transition],
+) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("the name cannot be empty".into());
+    }
+    if glyph.is_whitespace() || glyph.is_control() {
+        return Err(format!("the glyph {:?} is not visible", glyph));
+    }
+    check_depth(min_depth, max_depth)?;
+    check_table(spawn_weight).map_err(|reason| format!("spawn_weight: {}", reason))
+}
+
+/// 层数范围从 1 开始，并且不能是空的
+fn check_depth(min_depth: u32, max_depth: Option<u32>) -> Result<(), String> {
+    if min_depth == 0 {
+        return Err("min_depth starts at 1".into());
+    }
+    match max_depth {
+        Some(max_depth) if max_depth < min_depth => Err(format!(
+            "max_depth {} is less than min_depth {}",
+            max_depth, min_depth
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 在层数范围内时按表格取权重，否则为 0
+fn weight_at(level: u32, min_depth: u32, max_depth: Option<u32>, table: &[Transition]) -> u32 {
+    if level >= min_depth && max_depth.is_none_or(|max_depth| level <= max_depth) {
+        from_dungeon_level(table, level)
+    } else {
+        0
+    }
+}
+
+impl VaultTemplate {
+    pub fn width(&self) -> usize {
+        self.layout.first().map_or(0, |line| line.chars().count())
+    }
+
+    pub fn height(&self) -> usize {
+        self.layout.len()
+    }
+
+    /// 是否可以出现在第 `level` 层
+    pub fn allowed_at(&self, level: u32) -> bool {
+        level >= self.min_depth && self.max_depth.is_none_or(|max_depth| level <= max_depth)
+    }
+
+    /// 每一格的位置和字符，`(x, y)` 从左上角开始
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize, char)> + '_ {
+        self.layout.iter().enumerate().flat_map(|(y, line)| {
+            line.chars()
+                .enumerate()
+                .map(move |(x, glyph)| (x, y, glyph))
+        })
+    }
+
+    /// 从房间外面走不到的第一块地面
+    fn unreachable_tile(&self) -> Option<(usize, usize)> {
+        let (width, height) = (self.width(), self.height());
+        let grid: Vec<Vec<char>> = self
+            .layout
+            .iter()
+            .map(|line| line.chars().collect())
+            .collect();
+        let open = |x: usize, y: usize| Tile::new(vault_terrain(grid[y][x])).passable();
+
+        // 从边上的地面开始向内扩散
+        let on_edge = |x: usize, y: usize| x == 0 || y == 0 || x == width - 1 || y == height - 1;
+        let mut stack: Vec<(usize, usize)> = self
+            .cells()
+            .filter(|&(x, y, _)| on_edge(x, y) && open(x, y))
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        let mut reached = vec![vec![false; width]; height];
+        while let Some((x, y)) = stack.pop() {
+            if reached[y][x] {
+                continue;
+            }
+            reached[y][x] = true;
+            let neighbours = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            for (nx, ny) in neighbours {
+                if nx < width && ny < height && open(nx, ny) && !reached[ny][nx] {
+                    stack.push((nx, ny));
+                }
+            }
+        }
+
+        self.cells()
+            .find(|&(x, y, _)| open(x, y) && !reached[y][x])
+            .map(|(x, y, _)| (x, y))
+    }
+}
+
+impl MonsterTemplate {
+    /// 在第 `level` 层的出现权重，超出层数范围时为 0
+    pub fn weight_at(&self, level: u32) -> u32 {
+        weight_at(level, self.min_depth, self.max_depth, &self.spawn_weight)
+    }
+
+    /// 在 `(x, y)` 创建第 `level` 层的怪物
+    pub fn spawn(&self, x: i32, y: i32, level: u32) -> Object {
+        // 越深的怪物越强壮
+        let bonus = level as i32 - 1;
+        let mut monster = Object::new(x, y, self.glyph, &self.name, self.color, true);
+        monster.fighter = Some(Fighter {
+            base_max_hp: self.hp + 2 * bonus,
+            hp: self.hp + 2 * bonus,
+            base_defense: self.defense + bonus / 3,
+            base_power: self.power + bonus / 2,
+            xp: self.xp,
+            on_death: DeathCallback::Monster,
+        });
+        monster.alive = true;
+        monster.ai = Some(self.ai.create());
+        monster
+    }
+}
+
+impl AiKind {
+    fn create(self) -> Box<dyn Ai> {
+        match self {
+            AiKind::Basic => Box::new(BasicAi),
+        }
+    }
+}
+
+impl ItemTemplate {
+    /// 在第 `level` 层的出现权重，超出层数范围时为 0
+    pub fn weight_at(&self, level: u32) -> u32 {
+        weight_at(level, self.min_depth, self.max_depth, &self.spawn_weight)
+    }
+
+    /// 在 `(x, y)` 创建这个物品
+    pub fn spawn(&self, x: i32, y: i32) -> Object {
+        let mut object = Object::new(x, y, self.glyph, &self.name, self.color, false);
+        object.item = Some(self.item);
+        object.equipment = self.equipment.map(|equipment| Equipment {
+            slot: equipment.slot,
+            equipped: false,
+            max_hp_bonus: equipment.max_hp_bonus,
+            power_bonus: equipment.power_bonus,
+            defense_bonus: equipment.defense_bonus,
+        });
+        object
+    }
+}
@@ -0,0 +1,8 @@
+//! 各个测试共用的辅助函数
+
+use roguelike_rs::templates::Templates;
+
+/// 读取自带的模板
+pub fn templates() -> Templates {
+    Templates::load("data/templates.json").expect("the bundled templates are valid")
+}
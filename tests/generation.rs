@@ -0,0 +1,110 @@
+//! 地图生成的不变量：大小正确、边界封闭、所有地面从起点都走得到、房间不重叠
+
+mod common;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use common::templates;
+use roguelike_rs::game::new_game;
+use roguelike_rs::generation::{
+    create_room, make_map, validate_map, Bsp, Caves, DrunkardsWalk, Layout, LayoutError,
+    MapGenerator, RoomsAndCorridors,
+};
+use roguelike_rs::map::{Rect, Tile, TileKind, MAP_HEIGHT, MAP_WIDTH};
+use roguelike_rs::object::PLAYER;
+
+const SEEDS: u64 = 1000;
+
+#[test]
+fn every_generator_produces_valid_layouts() {
+    let generators: [(&str, &dyn MapGenerator); 4] = [
+        ("rooms", &RoomsAndCorridors),
+        ("bsp", &Bsp),
+        ("caves", &Caves),
+        ("drunkard", &DrunkardsWalk),
+    ];
+    for (name, generator) in generators {
+        for seed in 0..SEEDS {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let layout = generator.generate(&mut rng);
+            if let Err(err) = layout.validate() {
+                panic!("{} generator, seed {}: {}", name, seed, err);
+            }
+        }
+    }
+}
+
+#[test]
+fn finished_levels_are_connected() {
+    let templates = templates();
+    for seed in 0..2 * SEEDS {
+        // 轮流覆盖所有生成算法，以及出现预制房间的层数
+        let level = 1 + (seed % 8) as u32;
+        let mut objects = new_game(seed, &templates).1;
+        objects.truncate(PLAYER + 1);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let map = make_map(&mut objects, level, &templates, &mut rng);
+
+        let stairs = (0..MAP_WIDTH)
+            .flat_map(|x| (0..MAP_HEIGHT).map(move |y| (x, y)))
+            .find(|&(x, y)| map[x as usize][y as usize].kind == TileKind::Stairs)
+            .expect("every level has stairs");
+        if let Err(err) = validate_map(&map, objects[PLAYER].pos(), stairs) {
+            panic!("seed {}, level {}: {}", seed, level, err);
+        }
+    }
+}
+
+#[test]
+fn repair_fills_pockets_and_seals_the_border() {
+    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let main = Rect::new(5, 5, 10, 10);
+    let pocket = Rect::new(30, 5, 5, 5);
+    create_room(main, &mut map);
+    create_room(pocket, &mut map);
+    map[0][20] = Tile::empty();
+
+    let mut layout = Layout {
+        map,
+        start: main.center(),
+        stairs: (6, 6),
+        rooms: vec![main, pocket],
+    };
+    assert!(layout.validate().is_err());
+    layout.repair();
+    assert_eq!(layout.validate(), Ok(()));
+    assert!(layout.map[32][7].blocked());
+    assert!(layout.map[0][20].blocked());
+}
+
+#[test]
+fn unrepairable_layouts_are_rejected() {
+    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let room = Rect::new(5, 5, 10, 10);
+    create_room(room, &mut map);
+
+    // 楼梯在墙里
+    let mut layout = Layout {
+        map,
+        start: room.center(),
+        stairs: (40, 40),
+        rooms: vec![room],
+    };
+    layout.repair();
+    assert_eq!(
+        layout.validate(),
+        Err(LayoutError::BadStairs { x: 40, y: 40 })
+    );
+
+    // 房间内部重叠
+    layout.stairs = (6, 6);
+    layout.rooms.push(Rect::new(8, 8, 4, 4));
+    assert_eq!(
+        layout.validate(),
+        Err(LayoutError::RoomsOverlap {
+            first: 0,
+            second: 1
+        })
+    );
+}
@@ -0,0 +1,382 @@
+//! 不打开窗口运行游戏逻辑：`cargo test --no-default-features`
+
+mod common;
+
+use rand::Rng;
+
+use common::templates;
+use roguelike_rs::fov::Fov;
+use roguelike_rs::game::{
+    check_level_up, close_door, drop_item, level_up, level_up_xp, monsters_take_turn,
+    monsters_take_turns, new_game, next_level, player_move_or_attack, player_on_stairs, Game,
+    LevelUpChoice, LEVEL_UP_HP,
+};
+use roguelike_rs::items::{get_equipped_in_slot, use_item, Target, UseResult};
+use roguelike_rs::map::{Door, Tile, TileKind, MAP_HEIGHT, MAP_WIDTH};
+use roguelike_rs::object::{Item, Object, Slot, PLAYER};
+
+/// 所有位置都可见
+struct SeeEverything;
+
+impl Fov for SeeEverything {
+    fn is_in_fov(&self, _x: i32, _y: i32) -> bool {
+        true
+    }
+}
+
+/// 只有列出的位置可见
+struct SeeOnly(Vec<(i32, i32)>);
+
+impl Fov for SeeOnly {
+    fn is_in_fov(&self, x: i32, y: i32) -> bool {
+        self.0.contains(&(x, y))
+    }
+}
+
+#[test]
+fn same_seed_builds_same_dungeon() {
+    let (game_a, objects_a) = new_game(42, &templates());
+    let (game_b, objects_b) = new_game(42, &templates());
+
+    for x in 0..MAP_WIDTH as usize {
+        for y in 0..MAP_HEIGHT as usize {
+            assert_eq!(game_a.map[x][y].blocked(), game_b.map[x][y].blocked());
+        }
+    }
+    let positions = |objects: &[Object]| {
+        objects
+            .iter()
+            .map(|o| (o.name.clone(), o.pos()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(positions(&objects_a), positions(&objects_b));
+}
+
+#[test]
+fn gameplay_randomness_does_not_change_deeper_levels() {
+    let templates = templates();
+    let (mut game_a, mut objects_a) = new_game(42, &templates);
+    let (mut game_b, mut objects_b) = new_game(42, &templates);
+    // 例如混乱的怪物在第一层走了几步
+    for _ in 0..10 {
+        game_a.rng.gen_range(-1..=1);
+    }
+    next_level(&mut game_a, &mut objects_a, &templates);
+    next_level(&mut game_b, &mut objects_b, &templates);
+
+    for x in 0..MAP_WIDTH as usize {
+        for y in 0..MAP_HEIGHT as usize {
+            assert_eq!(game_a.map[x][y].kind, game_b.map[x][y].kind);
+        }
+    }
+    let positions = |objects: &[Object]| {
+        objects
+            .iter()
+            .map(|o| (o.name.clone(), o.pos()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(positions(&objects_a), positions(&objects_b));
+}
+
+#[test]
+fn player_starts_on_floor() {
+    let (game, objects) = new_game(7, &templates());
+    let (x, y) = objects[PLAYER].pos();
+    assert!(!game.map[x as usize][y as usize].blocked());
+}
+
+#[test]
+fn monsters_act_without_a_window() {
+    let (mut game, mut objects) = new_game(3, &templates());
+    let hp_before = objects[PLAYER].fighter.unwrap().hp;
+
+    // 只留下一只紧挨着玩家的怪物
+    let monster_id = objects
+        .iter()
+        .position(|o| o.ai.is_some())
+        .expect("seed 3 spawns monsters");
+    let mut monster = objects.swap_remove(monster_id);
+    objects.truncate(1);
+    let (x, y) = objects[PLAYER].pos();
+    monster.set_pos(x + 1, y);
+    objects.push(monster);
+
+    for _ in 0..20 {
+        monsters_take_turn(&SeeEverything, &mut game, &mut objects);
+    }
+    assert!(objects[PLAYER].fighter.unwrap().hp < hp_before);
+}
+
+#[test]
+fn doors_open_on_bump_and_close_on_command() {
+    let (mut game, mut objects) = new_game(5, &templates());
+    objects.truncate(1);
+    let (x, y) = objects[PLAYER].pos();
+    game.map[(x + 1) as usize][y as usize] = Tile::door();
+
+    // 撞门只开门，不移动
+    player_move_or_attack(1, 0, &mut game, &mut objects);
+    assert_eq!(objects[PLAYER].pos(), (x, y));
+    let door = game.map[(x + 1) as usize][y as usize];
+    assert_eq!(door.door_state(), Some(Door::Open));
+    assert!(!door.blocked() && !door.block_sight());
+    assert_eq!(game.changed_tiles, vec![(x + 1, y)]);
+
+    assert!(close_door(&mut game, &objects));
+    let door = game.map[(x + 1) as usize][y as usize];
+    assert_eq!(door.door_state(), Some(Door::Closed));
+    assert!(door.blocked() && door.block_sight());
+    assert_eq!(game.changed_tiles.len(), 2);
+}
+
+#[test]
+fn terrain_decides_movement_and_stairs() {
+    let (mut game, mut objects) = new_game(5, &templates());
+    objects.truncate(1);
+    let (x, y) = objects[PLAYER].pos();
+    game.map[(x + 1) as usize][y as usize] = Tile::new(TileKind::Lava);
+    game.map[x as usize][(y + 1) as usize] = Tile::new(TileKind::Water);
+
+    // 岩浆挡路但不挡视线，水可以走
+    player_move_or_attack(1, 0, &mut game, &mut objects);
+    assert_eq!(objects[PLAYER].pos(), (x, y));
+    assert!(!game.map[(x + 1) as usize][y as usize].block_sight());
+    player_move_or_attack(0, 1, &mut game, &mut objects);
+    assert_eq!(objects[PLAYER].pos(), (x, y + 1));
+
+    assert!(!player_on_stairs(&game, &objects));
+    game.map[x as usize][(y + 1) as usize] = Tile::new(TileKind::Stairs);
+    assert!(player_on_stairs(&game, &objects));
+}
+
+#[test]
+fn fireball_only_burns_what_the_player_can_see() {
+    let templates = templates();
+    let (mut game, mut objects) = new_game(5, &templates);
+    objects.truncate(1);
+    let (x, y) = objects[PLAYER].pos();
+    let orc = templates
+        .monster("orc")
+        .expect("the bundled templates have orcs");
+    objects.push(orc.spawn(x + 2, y, 1));
+    objects.push(orc.spawn(x + 4, y, 1));
+    let scroll = templates
+        .item("scroll of fireball")
+        .expect("the bundled templates have fireballs");
+    game.inventory.push(scroll.spawn(0, 0));
+
+    // 两只兽人都在爆炸范围内，但只看得见近的那只
+    let fov = SeeOnly(vec![(x, y), (x + 2, y)]);
+    let result = use_item(
+        0,
+        Some(Target::Tile(x + 2, y)),
+        &fov,
+        &mut game,
+        &mut objects,
+    );
+    assert_eq!(result, UseResult::UsedUp);
+    assert!(objects[1].fighter.is_none(), "the visible orc burns");
+    assert_eq!(
+        objects[2].fighter.map(|fighter| fighter.hp),
+        orc.spawn(0, 0, 1).fighter.map(|fighter| fighter.hp),
+        "the hidden orc is untouched"
+    );
+}
+
+#[test]
+fn wading_through_water_gives_monsters_extra_turns() {
+    let templates = templates();
+    let (mut game, mut objects) = new_game(5, &templates);
+    objects.truncate(1);
+    objects[PLAYER].set_pos(10, 10);
+    for x in 10..=20 {
+        game.map[x][10] = Tile::empty();
+    }
+    game.map[11][10] = Tile::new(TileKind::Water);
+    let orc = templates
+        .monster("orc")
+        .expect("the bundled templates have orcs");
+    objects.push(orc.spawn(20, 10, 1));
+
+    // 走进水里花两个回合，兽人走两步
+    let turns = player_move_or_attack(1, 0, &mut game, &mut objects);
+    assert_eq!(turns, 2);
+    monsters_take_turns(turns, &SeeEverything, &mut game, &mut objects);
+    assert_eq!(objects[1].pos(), (18, 10));
+
+    // 回到地面只花一个回合
+    let turns = player_move_or_attack(-1, 0, &mut game, &mut objects);
+    assert_eq!(turns, 1);
+    monsters_take_turns(turns, &SeeEverything, &mut game, &mut objects);
+    assert_eq!(objects[1].pos(), (17, 10));
+}
+
+#[test]
+fn fireball_targets_are_limited_to_its_range() {
+    let (_, mut objects) = new_game(5, &templates());
+    objects.truncate(1);
+    objects[PLAYER].set_pos(10, 10);
+    let request = Item::Fireball
+        .target_request()
+        .expect("fireballs need a target");
+
+    assert_eq!(
+        request.select(13, 10, &SeeEverything, &objects),
+        Some(Target::Tile(13, 10))
+    );
+    assert_eq!(request.select(30, 10, &SeeEverything, &objects), None);
+}
+
+/// 把模板中的物品放进背包，返回它在背包中的序号
+fn give(name: &str, game: &mut Game) -> usize {
+    let item = templates()
+        .item(name)
+        .unwrap_or_else(|| panic!("the bundled templates have a {}", name))
+        .spawn(0, 0);
+    game.inventory.push(item);
+    game.inventory.len() - 1
+}
+
+fn hp(objects: &[Object]) -> i32 {
+    objects[PLAYER].fighter.map_or(0, |fighter| fighter.hp)
+}
+
+#[test]
+fn gear_adds_to_base_stats() {
+    let (mut game, mut objects) = new_game(5, &templates());
+    let base = objects[PLAYER].fighter.expect("the player can fight");
+    for name in ["sword", "shield", "leather armor"] {
+        let id = give(name, &mut game);
+        let result = use_item(id, None, &SeeEverything, &mut game, &mut objects);
+        assert_eq!(result, UseResult::UsedAndKept);
+    }
+
+    let player = &objects[PLAYER];
+    assert_eq!(player.power(&game), base.base_power + 3);
+    assert_eq!(player.defense(&game), base.base_defense + 2);
+    assert_eq!(player.max_hp(&game), base.base_max_hp + 10);
+}
+
+#[test]
+fn equipping_a_taken_slot_replaces_the_old_item() {
+    let (mut game, mut objects) = new_game(5, &templates());
+    let first = give("sword", &mut game);
+    let second = give("sword", &mut game);
+    use_item(first, None, &SeeEverything, &mut game, &mut objects);
+    use_item(second, None, &SeeEverything, &mut game, &mut objects);
+
+    let equipped = |id: usize| game.inventory[id].equipment.is_some_and(|e| e.equipped);
+    assert!(!equipped(first));
+    assert!(equipped(second));
+    assert_eq!(
+        get_equipped_in_slot(Slot::RightHand, &game.inventory),
+        Some(second)
+    );
+}
+
+#[test]
+fn unequipping_keeps_hp_within_the_new_maximum() {
+    let (mut game, mut objects) = new_game(5, &templates());
+    let armor = give("leather armor", &mut game);
+    use_item(armor, None, &SeeEverything, &mut game, &mut objects);
+    objects[PLAYER].heal(100, &game);
+    assert_eq!(hp(&objects), 40);
+
+    use_item(armor, None, &SeeEverything, &mut game, &mut objects);
+    assert_eq!(objects[PLAYER].max_hp(&game), 30);
+    assert_eq!(hp(&objects), 30);
+}
+
+#[test]
+fn dropping_equipped_gear_keeps_hp_within_the_new_maximum() {
+    let (mut game, mut objects) = new_game(5, &templates());
+    let armor = give("leather armor", &mut game);
+    use_item(armor, None, &SeeEverything, &mut game, &mut objects);
+    objects[PLAYER].heal(100, &game);
+
+    drop_item(armor, &mut game, &mut objects);
+    assert_eq!(objects[PLAYER].max_hp(&game), 30);
+    assert_eq!(hp(&objects), 30);
+}
+
+/// 在玩家右边放一只兽人，返回它的序号
+fn orc_next_to_player(objects: &mut Vec<Object>) -> usize {
+    let (x, y) = objects[PLAYER].pos();
+    let orc = templates()
+        .monster("orc")
+        .expect("the bundled templates have orcs")
+        .spawn(x + 1, y, 1);
+    objects.push(orc);
+    objects.len() - 1
+}
+
+fn xp(objects: &[Object]) -> i32 {
+    objects[PLAYER].fighter.map_or(0, |fighter| fighter.xp)
+}
+
+fn set_xp(objects: &mut [Object], xp: i32) {
+    if let Some(fighter) = objects[PLAYER].fighter.as_mut() {
+        fighter.xp = xp;
+    }
+}
+
+#[test]
+fn reaching_the_threshold_levels_up() {
+    let (mut game, mut objects) = new_game(5, &templates());
+    set_xp(&mut objects, level_up_xp(1) - 1);
+    assert!(!check_level_up(&mut game, &mut objects));
+
+    set_xp(&mut objects, level_up_xp(1) + 5);
+    assert!(check_level_up(&mut game, &mut objects));
+    assert_eq!(objects[PLAYER].level, 2);
+    assert_eq!(xp(&objects), 5);
+    assert!(!check_level_up(&mut game, &mut objects));
+}
+
+#[test]
+fn enough_xp_for_several_levels_levels_up_on_each_call() {
+    let (mut game, mut objects) = new_game(5, &templates());
+    set_xp(&mut objects, level_up_xp(1) + level_up_xp(2));
+    assert!(check_level_up(&mut game, &mut objects));
+    assert!(check_level_up(&mut game, &mut objects));
+    assert!(!check_level_up(&mut game, &mut objects));
+    assert_eq!(objects[PLAYER].level, 3);
+    assert_eq!(xp(&objects), 0);
+}
+
+#[test]
+fn constitution_raises_max_and_current_hp() {
+    let (_, mut objects) = new_game(5, &templates());
+    let before = objects[PLAYER].fighter.expect("the player can fight");
+    level_up(LevelUpChoice::Hp, &mut objects);
+    let after = objects[PLAYER].fighter.expect("the player can fight");
+    assert_eq!(after.base_max_hp, before.base_max_hp + LEVEL_UP_HP);
+    assert_eq!(after.hp, before.hp + LEVEL_UP_HP);
+}
+
+#[test]
+fn killing_in_melee_gives_the_monster_xp() {
+    let (mut game, mut objects) = new_game(5, &templates());
+    objects.truncate(1);
+    let orc = orc_next_to_player(&mut objects);
+    let reward = objects[orc].fighter.expect("orcs can fight").xp;
+
+    while objects[orc].alive {
+        player_move_or_attack(1, 0, &mut game, &mut objects);
+    }
+    assert_eq!(xp(&objects), reward);
+}
+
+#[test]
+fn killing_with_lightning_gives_the_monster_xp() {
+    let (mut game, mut objects) = new_game(5, &templates());
+    objects.truncate(1);
+    let orc = orc_next_to_player(&mut objects);
+    let reward = objects[orc].fighter.expect("orcs can fight").xp;
+    let scroll = give("scroll of lightning bolt", &mut game);
+
+    let result = use_item(scroll, None, &SeeEverything, &mut game, &mut objects);
+    assert_eq!(result, UseResult::UsedUp);
+    assert!(!objects[orc].alive);
+    assert_eq!(xp(&objects), reward);
+}
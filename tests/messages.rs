@@ -0,0 +1,34 @@
+//! 消息按宽度折行
+
+use roguelike_rs::messages::wrap;
+
+#[test]
+fn wraps_at_spaces() {
+    assert_eq!(
+        wrap("The orc hits you for 3 damage.", 12),
+        vec!["The orc hits", "you for 3", "damage."]
+    );
+}
+
+#[test]
+fn long_words_are_split() {
+    assert_eq!(
+        wrap("a fireballfireball b", 8),
+        vec!["a", "fireball", "fireball", "b"]
+    );
+    assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+}
+
+#[test]
+fn empty_message_is_one_empty_line() {
+    assert_eq!(wrap("", 10), vec![String::new()]);
+    assert_eq!(wrap("   ", 10), vec![String::new()]);
+}
+
+#[test]
+fn repeated_spaces_collapse() {
+    assert_eq!(
+        wrap("  You   feel\tbetter.  ", 40),
+        vec!["You feel better."]
+    );
+}
@@ -0,0 +1,66 @@
+//! 放置对象时不能重叠，也不能放进墙里
+
+mod common;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashSet;
+
+use common::templates;
+use roguelike_rs::game::new_game;
+use roguelike_rs::generation::{create_room, make_map, place_objects};
+use roguelike_rs::map::{Map, Rect, Tile, MAP_HEIGHT, MAP_WIDTH};
+use roguelike_rs::object::{Object, PLAYER};
+
+/// 检查阻挡对象都在地板上，并且没有两个共享同一格
+fn assert_no_blocking_overlap(map: &Map, objects: &[Object]) {
+    let mut taken = HashSet::new();
+    for object in objects.iter().filter(|object| object.blocks) {
+        let (x, y) = object.pos();
+        assert!(
+            !map[x as usize][y as usize].blocked(),
+            "{} placed inside a wall at {:?}",
+            object.name,
+            (x, y)
+        );
+        assert!(
+            taken.insert((x, y)),
+            "{} shares {:?} with another blocking object",
+            object.name,
+            (x, y)
+        );
+    }
+}
+
+#[test]
+fn blocking_objects_never_share_a_tile() {
+    let templates = templates();
+    for seed in 0..200 {
+        for level in 1..=10 {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let mut objects = new_game(seed, &templates).1;
+            objects.truncate(PLAYER + 1);
+            let map = make_map(&mut objects, level, &templates, &mut rng);
+            assert_no_blocking_overlap(&map, &objects);
+        }
+    }
+}
+
+#[test]
+fn crowded_room_skips_taken_tiles() {
+    let templates = templates();
+    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    // 内部只有 2x2 格的小房间，玩家占据其中一格
+    let room = Rect::new(10, 10, 3, 3);
+    create_room(room, &mut map);
+    let mut objects = new_game(1, &templates).1;
+    objects.truncate(PLAYER + 1);
+    objects[PLAYER].set_pos(11, 11);
+
+    let mut rng = ChaCha8Rng::seed_from_u64(5);
+    for _ in 0..50 {
+        place_objects(room, &map, &mut objects, 9, &templates, &mut rng);
+    }
+    assert_no_blocking_overlap(&map, &objects);
+    assert_eq!(objects[PLAYER].pos(), (11, 11));
+}
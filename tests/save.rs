@@ -0,0 +1,87 @@
+//! 存档读写：内容原样恢复，损坏和版本不符的存档给出明确的错误
+
+mod common;
+
+use rand::Rng;
+use std::fs;
+use std::path::PathBuf;
+
+use common::templates;
+use roguelike_rs::game::new_game;
+use roguelike_rs::object::PLAYER;
+use roguelike_rs::save::{load_game, save_game, SaveError, SAVE_VERSION};
+
+/// 每个测试使用自己的临时文件，测试可以并行运行
+fn save_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("roguelike-rs-{}-{}.json", name, std::process::id()))
+}
+
+#[test]
+fn save_round_trips_the_whole_game() {
+    let (mut game, objects) = new_game(11, &templates());
+    game.map[3][4].explored = true;
+    game.map[40][20].explored = true;
+    assert!(
+        objects.iter().any(|object| object.ai.is_some()),
+        "seed 11 spawns monsters"
+    );
+
+    let path = save_path("round-trip");
+    save_game(&path, &game, &objects).expect("the game can be saved");
+    let (mut loaded_game, loaded_objects) = load_game(&path).expect("the save can be loaded");
+    fs::remove_file(&path).unwrap();
+
+    // 地图（包括 explored）、消息和对象（包括 `Box<dyn Ai>`）都原样恢复
+    assert_eq!(
+        serde_json::to_string(&loaded_game).unwrap(),
+        serde_json::to_string(&game).unwrap()
+    );
+    assert_eq!(
+        serde_json::to_string(&loaded_objects).unwrap(),
+        serde_json::to_string(&objects).unwrap()
+    );
+    assert!(loaded_game.map[3][4].explored && loaded_game.map[40][20].explored);
+    assert_eq!(loaded_objects[PLAYER].name, objects[PLAYER].name);
+
+    // 随机数生成器从同一位置继续
+    let expected: Vec<u32> = (0..8).map(|_| game.rng.gen()).collect();
+    let actual: Vec<u32> = (0..8).map(|_| loaded_game.rng.gen()).collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn other_versions_are_rejected() {
+    let path = save_path("version");
+    fs::write(
+        &path,
+        format!(r#"{{"version": {}, "game": null}}"#, SAVE_VERSION + 1),
+    )
+    .unwrap();
+    let result = load_game(&path);
+    fs::remove_file(&path).unwrap();
+
+    match result {
+        Err(SaveError::IncompatibleVersion { found, expected }) => {
+            assert_eq!(found, SAVE_VERSION + 1);
+            assert_eq!(expected, SAVE_VERSION);
+        }
+        other => panic!("expected a version error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn corrupt_saves_are_rejected() {
+    let (game, objects) = new_game(11, &templates());
+    let path = save_path("corrupt");
+    save_game(&path, &game, &objects).unwrap();
+    // 截断文件，版本号仍然完整
+    let data = fs::read_to_string(&path).unwrap();
+    fs::write(&path, &data[..data.len() / 2]).unwrap();
+    let truncated = load_game(&path);
+    fs::write(&path, "not a save").unwrap();
+    let garbage = load_game(&path);
+    fs::remove_file(&path).unwrap();
+
+    assert!(matches!(truncated, Err(SaveError::Corrupt(_))));
+    assert!(matches!(garbage, Err(SaveError::Corrupt(_))));
+}
@@ -0,0 +1,54 @@
+//! 随层数变化的数值和出现表的概率
+
+use roguelike_rs::spawn::{check_table, from_dungeon_level, SpawnTable, Transition};
+
+fn table() -> Vec<Transition> {
+    vec![
+        Transition { level: 2, value: 5 },
+        Transition {
+            level: 4,
+            value: 15,
+        },
+    ]
+}
+
+#[test]
+fn values_follow_the_last_transition_reached() {
+    let table = table();
+    // 第一项之前为 0
+    assert_eq!(from_dungeon_level(&table, 1), 0);
+    // 正好在变化的那一层
+    assert_eq!(from_dungeon_level(&table, 2), 5);
+    assert_eq!(from_dungeon_level(&table, 3), 5);
+    assert_eq!(from_dungeon_level(&table, 4), 15);
+    // 最后一项之后保持不变
+    assert_eq!(from_dungeon_level(&table, 100), 15);
+}
+
+#[test]
+fn tables_may_start_deeper_but_must_increase() {
+    assert_eq!(check_table(&table()), Ok(()));
+    assert!(check_table(&[]).is_err());
+    assert!(check_table(&[Transition { level: 0, value: 1 }]).is_err());
+
+    let mut unordered = table();
+    unordered.reverse();
+    assert_eq!(
+        check_table(&unordered),
+        Err("levels must increase, got 2 after 4".to_string())
+    );
+}
+
+#[test]
+fn probabilities_skip_zero_weights() {
+    let table = SpawnTable::new(vec![("orc", 60), ("dragon", 0), ("troll", 20)]);
+    let probabilities = table.probabilities();
+    assert_eq!(probabilities, vec![(&"orc", 0.75), (&"troll", 0.25)]);
+}
+
+#[test]
+fn all_zero_weights_choose_nothing() {
+    let table = SpawnTable::new(vec![("orc", 0), ("troll", 0)]);
+    assert!(table.probabilities().is_empty());
+    assert_eq!(table.choose(&mut rand::thread_rng()), None);
+}
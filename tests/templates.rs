@@ -0,0 +1,80 @@
+//! 模板检查：错误要指出是哪个列表中的哪一项
+
+use serde_json::{json, Value};
+use std::fs;
+
+use roguelike_rs::templates::{TemplateError, Templates};
+
+/// 自带的数据文件，测试在它的基础上改出有问题的模板
+fn bundled() -> Value {
+    let text = fs::read_to_string("data/templates.json").expect("the bundled templates exist");
+    serde_json::from_str(&text).expect("the bundled templates are valid JSON")
+}
+
+/// 检查模板，期望 `section[index]` 的 `name` 出错，原因中包含 `reason`
+fn assert_invalid(templates: Value, section: &str, index: usize, name: &str, reason: &str) {
+    match Templates::from_json(&templates.to_string()) {
+        Err(TemplateError::Invalid {
+            section: found_section,
+            index: found_index,
+            name: found_name,
+            reason: found_reason,
+        }) => {
+            assert_eq!(
+                (found_section, found_index, found_name.as_str()),
+                (section, index, name)
+            );
+            assert!(found_reason.contains(reason), "{}", found_reason);
+        }
+        Err(err) => panic!("expected {}[{}] to be invalid, got {}", section, index, err),
+        Ok(_) => panic!("expected {}[{}] to be invalid", section, index),
+    }
+}
+
+#[test]
+fn bundled_templates_are_valid() {
+    assert!(Templates::from_json(&bundled().to_string()).is_ok());
+}
+
+#[test]
+fn monster_without_hp_is_rejected() {
+    let mut templates = bundled();
+    templates["monsters"][1]["hp"] = json!(0);
+    assert_invalid(templates, "monsters", 1, "troll", "hp must be positive");
+}
+
+#[test]
+fn duplicate_names_are_rejected() {
+    let mut templates = bundled();
+    templates["items"][2]["name"] = json!("healing potion");
+    assert_invalid(
+        templates,
+        "items",
+        2,
+        "healing potion",
+        "used by another item",
+    );
+}
+
+#[test]
+fn equipment_needs_an_equipment_entry() {
+    let mut templates = bundled();
+    let sword = templates["items"][4]
+        .as_object_mut()
+        .expect("items are objects");
+    sword.remove("equipment");
+    assert_invalid(
+        templates,
+        "items",
+        4,
+        "sword",
+        "need an \"equipment\" entry",
+    );
+}
+
+#[test]
+fn vault_with_unreachable_floor_is_rejected() {
+    let mut templates = bundled();
+    templates["vaults"][0]["layout"] = json!(["#####", "#.#.#", "#####"]);
+    assert_invalid(templates, "vaults", 0, "guard post", "cannot be reached");
+}